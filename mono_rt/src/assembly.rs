@@ -1,22 +1,51 @@
-use crate::{Domain, MonoClass};
-use anyhow::Result;
-use std::ffi::CString;
-use std::ptr::NonNull;
+use crate::domain::image_status_message;
+use crate::iter::Types;
+use crate::{osstr_to_cstring, Domain, Method, MethodDesc, MonoClass};
+use anyhow::{anyhow, Result};
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr::{null, NonNull};
 
 #[non_exhaustive]
-pub struct Assembly {
+pub struct Assembly<'d> {
     pub(crate) raw: NonNull<mono_sys::MonoAssembly>,
     pub(crate) path_cstr: CString,
+    pub(crate) _m: PhantomData<&'d Domain>,
 }
 
-impl Assembly {
+impl<'d> Assembly<'d> {
+    /// An assembly mono handed out, the path is taken from its image
+    pub(crate) fn from_raw(raw: NonNull<sys::MonoAssembly>) -> Self {
+        let path_cstr = unsafe {
+            let image = sys::mono_assembly_get_image(raw.as_ptr());
+            let ptr = if image.is_null() {
+                std::ptr::null()
+            } else {
+                sys::mono_image_get_filename(image)
+            };
+            if ptr.is_null() {
+                CString::default()
+            } else {
+                CStr::from_ptr(ptr).to_owned()
+            }
+        };
+        Self {
+            raw,
+            path_cstr,
+            _m: PhantomData,
+        }
+    }
+
     unsafe fn exec_helper(&self, args: &[CString]) -> i32 {
         let mut v = vec![self.path_cstr.clone().into_raw()];
         v.extend(args.to_vec().into_iter().map(|s| s.into_raw()));
 
         mono_sys::mono_jit_exec(
             // self.domain.raw.as_ptr(),
-            Domain::get().unwrap().raw.as_ptr(),
+            Domain::current().unwrap().raw.as_ptr(),
             self.raw.as_ptr(),
             v.len() as i32,
             v.as_mut_ptr(),
@@ -43,31 +72,331 @@ impl Assembly {
         Ok(unsafe { self.exec_helper(args.as_slice()) })
     }
 
-    pub fn get_image(&self) -> Image {
+    pub fn get_name(&self) -> AssemblyName<'d> {
+        let ptr = unsafe { sys::mono_assembly_get_name(self.raw.as_ptr()) };
+        AssemblyName::new(NonNull::new(ptr).unwrap())
+    }
+
+    /// The assemblies this one references, in the order of its metadata
+    pub fn references(&self) -> Vec<AssemblyName<'d>> {
+        let image = self.get_image().raw.as_ptr();
+        let rows = unsafe {
+            sys::mono_image_get_table_rows(
+                image,
+                sys::MonoMetaTableEnum_MONO_TABLE_ASSEMBLYREF as _,
+            )
+        };
+        (0..rows)
+            .filter_map(|index| unsafe {
+                // the parsed placeholder strings are freed, mono points the name
+                // into the metadata of the image instead
+                let name = alloc_name(CStr::from_bytes_with_nul(b"_\0").unwrap())?;
+                sys::mono_assembly_name_free(name.as_ptr());
+                sys::mono_assembly_get_assemblyref(image, index, name.as_ptr());
+                Some(AssemblyName::with_storage(name, NameStorage::Metadata))
+            })
+            .collect()
+    }
+
+    /// Loads every referenced assembly now instead of on first use,
+    /// failing with the names of those that could not be loaded
+    ///
+    /// References are resolved like the runtime does, next to this assembly and then
+    /// in the assembly search paths, with framework assemblies remapped to the loaded
+    /// versions and the load hooks run
+    pub fn load_references(&self) -> Result<()> {
+        let image = self.get_image().raw.as_ptr();
+        // the runtime looks for references in the directory of the referencing assembly
+        let basedir = unsafe {
+            let filename = sys::mono_image_get_filename(image);
+            if filename.is_null() {
+                None
+            } else {
+                let path = CStr::from_ptr(filename).to_string_lossy().into_owned();
+                Path::new(&path)
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .map(|dir| osstr_to_cstring(dir.as_os_str()))
+                    .transpose()?
+            }
+        };
+
+        let missing: Vec<String> = self
+            .references()
+            .into_iter()
+            .filter_map(|name| {
+                let mut status = sys::MonoImageOpenStatus_MONO_IMAGE_OK;
+                let loaded = unsafe {
+                    sys::mono_assembly_load(
+                        name.raw.as_ptr(),
+                        basedir.as_ref().map_or(null(), |dir| dir.as_ptr()),
+                        &mut status,
+                    )
+                };
+                if loaded.is_null() {
+                    Some(format!("{} ({})", name, image_status_message(status)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("Failed to load {}", missing.join(", ")));
+        }
+        Ok(())
+    }
+
+    pub fn get_image(&self) -> Image<'d> {
         let ptr = unsafe { sys::mono_assembly_get_image(self.raw.as_ptr()) };
         let raw = NonNull::new(ptr).unwrap();
-        Image { raw }
+        Image {
+            raw,
+            _m: PhantomData,
+        }
+    }
+}
+
+/// The identity of an assembly, or of a reference to one
+///
+/// Formats as the full display name, like
+/// `System, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089`
+pub struct AssemblyName<'d> {
+    pub(crate) raw: NonNull<sys::MonoAssemblyName>,
+    storage: NameStorage,
+    _m: PhantomData<&'d Domain>,
+}
+
+/// Who frees a name and its strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NameStorage {
+    /// The name of a loaded assembly, owned by the runtime
+    Runtime,
+    /// Parsed by us, both the name and its strings are freed on drop
+    Owned,
+    /// Allocated by us but pointing into the metadata of an image,
+    /// only the name itself is freed on drop
+    Metadata,
+}
+
+/// Allocates a name through mono by parsing `name`
+///
+/// `MonoAssemblyName` is opaque in the bindings and has no size, so it can not be
+/// allocated from rust. The name is freed with `mono_free`, its strings with
+/// `mono_assembly_name_free`.
+fn alloc_name(name: &CStr) -> Option<NonNull<sys::MonoAssemblyName>> {
+    NonNull::new(unsafe { sys::mono_assembly_name_new(name.as_ptr()) })
+}
+
+impl<'d> AssemblyName<'d> {
+    pub(crate) fn new(raw: NonNull<sys::MonoAssemblyName>) -> Self {
+        Self::with_storage(raw, NameStorage::Runtime)
+    }
+
+    fn with_storage(raw: NonNull<sys::MonoAssemblyName>, storage: NameStorage) -> Self {
+        Self {
+            raw,
+            storage,
+            _m: PhantomData,
+        }
+    }
+
+    /// Parses a simple or full name, like `System` or `System, Version=4.0.0.0`
+    pub fn parse(name: &str) -> Result<Self> {
+        let cstr = CString::new(name)?;
+        alloc_name(&cstr)
+            .map(|raw| Self::with_storage(raw, NameStorage::Owned))
+            .ok_or_else(|| anyhow!("{} is not a valid assembly name", name))
+    }
+
+    /// The simple name, like `System.Core`
+    pub fn name(&self) -> String {
+        let ptr = unsafe { sys::mono_assembly_name_get_name(self.raw.as_ptr()) };
+        if ptr.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// The culture, `None` for neutral assemblies
+    pub fn culture(&self) -> Option<String> {
+        let ptr = unsafe { sys::mono_assembly_name_get_culture(self.raw.as_ptr()) };
+        if ptr.is_null() {
+            return None;
+        }
+        let culture = unsafe { CStr::from_ptr(ptr) }.to_string_lossy();
+        if culture.is_empty() || culture == "neutral" {
+            None
+        } else {
+            Some(culture.into_owned())
+        }
+    }
+
+    /// Major, minor, build and revision, all zero if the reference has no version
+    pub fn version(&self) -> (u16, u16, u16, u16) {
+        let (mut minor, mut build, mut revision) = (0, 0, 0);
+        let major = unsafe {
+            sys::mono_assembly_name_get_version(
+                self.raw.as_ptr(),
+                &mut minor,
+                &mut build,
+                &mut revision,
+            )
+        };
+        (major, minor, build, revision)
+    }
+
+    /// The public key token as 16 hex digits, `None` for unsigned assemblies
+    pub fn public_key_token(&self) -> Option<String> {
+        let ptr = unsafe { sys::mono_assembly_name_get_pubkeytoken(self.raw.as_ptr()) };
+        if ptr.is_null() {
+            return None;
+        }
+        let token = unsafe { CStr::from_ptr(ptr as *const _) }.to_string_lossy();
+        if token.is_empty() {
+            None
+        } else {
+            Some(token.into_owned())
+        }
+    }
+
+    pub fn full_name(&self) -> String {
+        unsafe {
+            let ptr = sys::mono_stringify_assembly_name(self.raw.as_ptr());
+            if ptr.is_null() {
+                return String::new();
+            }
+            let name = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+            sys::mono_free(ptr as *mut _);
+            name
+        }
+    }
+}
+
+impl Drop for AssemblyName<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            match self.storage {
+                NameStorage::Runtime => {}
+                NameStorage::Owned => {
+                    sys::mono_assembly_name_free(self.raw.as_ptr());
+                    sys::mono_free(self.raw.as_ptr() as *mut _);
+                }
+                NameStorage::Metadata => sys::mono_free(self.raw.as_ptr() as *mut _),
+            }
+        }
+    }
+}
+
+impl PartialEq for AssemblyName<'_> {
+    /// Compares name, culture, public key token and version
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { sys::mono_assembly_names_equal(self.raw.as_ptr(), other.raw.as_ptr()) != 0 }
+    }
+}
+
+impl fmt::Display for AssemblyName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.full_name())
+    }
+}
+
+impl fmt::Debug for AssemblyName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AssemblyName")
+            .field(&self.full_name())
+            .finish()
     }
 }
 
 #[non_exhaustive]
 #[repr(transparent)]
-pub struct Image {
-    raw: NonNull<sys::MonoImage>,
+pub struct Image<'d> {
+    pub(crate) raw: NonNull<sys::MonoImage>,
+    _m: PhantomData<&'d Domain>,
 }
 
-impl Image {
-    pub fn get_class(&self, namespace: &str, name: &str) -> Option<MonoClass> {
+impl<'d> Image<'d> {
+    /// The image of `mscorlib`
+    pub fn corlib(_domain: &'d Domain) -> Image<'d> {
+        let ptr = unsafe { sys::mono_get_corlib() };
+        Image {
+            raw: NonNull::new(ptr).unwrap(),
+            _m: PhantomData,
+        }
+    }
+
+    /// The assembly the image is the manifest module of
+    pub fn get_assembly(&self) -> Option<Assembly<'d>> {
+        let ptr = unsafe { sys::mono_image_get_assembly(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(Assembly::from_raw)
+    }
+
+    /// Looks up a method by a description like `App.Program:PrintNumber(uint,uint)`,
+    /// see [`MethodDesc`](crate::MethodDesc)
+    pub fn get_method_from_desc(&self, desc: &str) -> Option<Method<'d>> {
+        MethodDesc::new(desc).ok()?.search_in_image(self)
+    }
+
+    /// Every type defined in the image, nested types and the `<Module>` type included
+    pub fn types(&self) -> Types<'d> {
+        Types::new(self)
+    }
+
+    /// Looks up a class ignoring case, see [`get_class_exact`](Self::get_class_exact)
+    pub fn get_class(&self, namespace: &str, name: &str) -> Option<MonoClass<'d>> {
+        self.class_from_name(namespace, name, sys::mono_class_from_name_case)
+    }
+
+    /// Looks up a class by its exact namespace and name,
+    /// nested types are named `Outer/Inner`
+    pub fn get_class_exact(&self, namespace: &str, name: &str) -> Option<MonoClass<'d>> {
+        self.class_from_name(namespace, name, sys::mono_class_from_name)
+    }
+
+    /// Looks up a class by its TypeDef, TypeRef or TypeSpec metadata token
+    pub fn get_class_from_token(&self, token: u32) -> Option<MonoClass<'d>> {
+        let ptr = unsafe { sys::mono_class_get(self.raw.as_ptr(), token) };
+        NonNull::new(ptr).map(MonoClass::new)
+    }
+
+    fn class_from_name(
+        &self,
+        namespace: &str,
+        name: &str,
+        lookup: unsafe extern "C" fn(
+            *mut sys::MonoImage,
+            *const c_char,
+            *const c_char,
+        ) -> *mut sys::MonoClass,
+    ) -> Option<MonoClass<'d>> {
         let namespace_cstr = CString::new(namespace).ok()?;
         let name_cstr = CString::new(name).ok()?;
         let ptr = unsafe {
-            sys::mono_class_from_name_case(
+            lookup(
                 self.raw.as_ptr(),
                 namespace_cstr.as_ptr(),
                 name_cstr.as_ptr(),
             )
         };
-        let raw = NonNull::new(ptr)?;
-        Some(MonoClass::new(raw))
+        NonNull::new(ptr).map(MonoClass::new)
     }
 }
+
+/// Searches the assemblies loaded into `domain` for the class `namespace.name`
+pub(crate) fn find_loaded_class<'d>(
+    domain: &'d Domain,
+    namespace: &str,
+    name: &str,
+) -> Option<MonoClass<'d>> {
+    let namespace = CString::new(namespace).ok()?;
+    let name = CString::new(name).ok()?;
+    domain.assemblies().ok()?.iter().find_map(|assembly| {
+        let image = assembly.get_image();
+        let ptr = unsafe {
+            sys::mono_class_from_name(image.raw.as_ptr(), namespace.as_ptr(), name.as_ptr())
+        };
+        NonNull::new(ptr).map(MonoClass::new)
+    })
+}
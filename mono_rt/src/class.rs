@@ -1,29 +1,37 @@
-use crate::{Domain, MonoObject, MonoType};
-use std::ffi::{c_void, CString};
+use crate::convert;
+use crate::exception::{self, MonoException};
+use crate::iter::{Events, Fields, Interfaces, Methods, NestedTypes, Properties};
+use crate::{Domain, FromMono, Image, MethodArgs, MonoEvent, MonoObject, MonoType, ToMono};
+use anyhow::{anyhow, Result};
+use std::ffi::{c_void, CStr, CString};
 use std::marker::PhantomData;
 use std::ptr::{null_mut, NonNull};
 
 #[non_exhaustive]
 #[repr(transparent)]
-pub struct MonoClass {
+pub struct MonoClass<'d> {
     pub(crate) raw: NonNull<sys::MonoClass>,
+    _m: PhantomData<&'d Domain>,
 }
 
-impl MonoClass {
+impl<'d> MonoClass<'d> {
     pub(crate) fn new(raw: NonNull<sys::MonoClass>) -> Self {
-        Self { raw }
+        Self {
+            raw,
+            _m: PhantomData,
+        }
     }
 
-    pub fn get_field_from_name(&self, name: &str) -> Option<MonoClassField> {
+    pub fn get_field_from_name(&self, name: &str) -> Option<MonoClassField<'d>> {
         let cstr = CString::new(name).ok()?;
 
         let ptr = unsafe { sys::mono_class_get_field_from_name(self.raw.as_ptr(), cstr.as_ptr()) };
         let raw = NonNull::new(ptr)?;
 
-        Some(MonoClassField { raw })
+        Some(MonoClassField::new(raw))
     }
 
-    pub fn get_method_from_name(&self, name: &str, param_count: i32) -> Option<Method> {
+    pub fn get_method_from_name(&self, name: &str, param_count: i32) -> Option<Method<'d>> {
         let name_cstr = CString::new(name).ok()?;
 
         let ptr = unsafe {
@@ -34,39 +42,194 @@ impl MonoClass {
         Some(Method::new(raw))
     }
 
-    pub fn get_parent(&self) -> Option<MonoClass> {
+    pub fn get_property_from_name(&self, name: &str) -> Option<MonoProperty<'d>> {
+        let cstr = CString::new(name).ok()?;
+        let ptr =
+            unsafe { sys::mono_class_get_property_from_name(self.raw.as_ptr(), cstr.as_ptr()) };
+        NonNull::new(ptr).map(MonoProperty::new)
+    }
+
+    /// The properties declared by the class itself, not the ones it inherits
+    pub fn get_properties(&self) -> Properties<'d> {
+        Properties::new(self)
+    }
+
+    /// The events declared by the class itself, not the ones it inherits
+    pub fn get_events(&self) -> Events<'d> {
+        Events::new(self)
+    }
+
+    pub fn get_event_from_name(&self, name: &str) -> Option<MonoEvent<'d>> {
+        self.get_events().find(|event| event.get_name() == name)
+    }
+
+    /// Looks up a method by a description like `Program:PrintNumber(uint,uint)`,
+    /// see [`MethodDesc`]
+    pub fn get_method_from_desc(&self, desc: &str) -> Option<Method<'d>> {
+        MethodDesc::new(desc).ok()?.search_in_class(self)
+    }
+
+    /// Looks up the overload of a method declared by this class taking exactly `params`
+    pub fn get_method_from_params(
+        &self,
+        name: &str,
+        params: &[MonoType<'_>],
+    ) -> Option<Method<'d>> {
+        self.get_methods().find(|method| {
+            if method.get_name_cstr().to_bytes() != name.as_bytes() {
+                return false;
+            }
+            let signature = method.signature();
+            let method_params = signature.params();
+            method_params.len() == params.len()
+                && method_params.iter().zip(params).all(|(a, b)| unsafe {
+                    sys::mono_metadata_type_equal(a.raw.as_ptr(), b.raw.as_ptr()) != 0
+                })
+        })
+    }
+
+    pub fn get_parent(&self) -> Option<MonoClass<'d>> {
         let ptr = unsafe { sys::mono_class_get_parent(self.raw.as_ptr()) };
         let raw = NonNull::new(ptr)?;
         Some(MonoClass::new(raw))
     }
 
-    pub fn get_methods(&self) -> Vec<Method> {
-        let mut methods = Vec::new();
-        unsafe {
-            let iter_ptr = Box::into_raw(Box::new(Box::into_raw(Box::new(0)))) as *mut *mut _;
-            loop {
-                let x = sys::mono_class_get_methods(self.raw.as_ptr(), iter_ptr);
-                if x.is_null() {
-                    break;
-                }
-                let raw = NonNull::new_unchecked(x);
-                methods.push(Method::new(raw))
-            }
-        }
-        methods
+    pub fn get_methods(&self) -> Methods<'d> {
+        Methods::new(self)
     }
 
-    pub fn get_type(&self) -> MonoType {
+    pub fn get_type(&self) -> MonoType<'d> {
         let ptr = unsafe { sys::mono_class_get_type(self.raw.as_ptr()) };
         let raw = NonNull::new(ptr).unwrap();
-        MonoType { raw }
+        MonoType::new(raw)
+    }
+
+    /// The metadata token of the class in its image, see [`Image::get_class_from_token`]
+    pub fn get_type_token(&self) -> u32 {
+        unsafe { sys::mono_class_get_type_token(self.raw.as_ptr()) }
+    }
+
+    pub fn get_name(&self) -> String {
+        self.get_name_cstr().to_string_lossy().into_owned()
+    }
+
+    /// The name as mono stores it, without a copy
+    pub fn get_name_cstr(&self) -> &'d CStr {
+        unsafe { CStr::from_ptr(sys::mono_class_get_name(self.raw.as_ptr())) }
+    }
+
+    /// Empty for classes outside of a namespace
+    pub fn get_namespace(&self) -> String {
+        let cstr = unsafe { CStr::from_ptr(sys::mono_class_get_namespace(self.raw.as_ptr())) };
+        cstr.to_string_lossy().into_owned()
+    }
+
+    /// Instance and static fields declared by the class itself
+    pub fn get_fields(&self) -> Fields<'d> {
+        Fields::new(self)
+    }
+
+    /// The interfaces the class declares to implement, not the ones it inherits
+    pub fn get_interfaces(&self) -> Interfaces<'d> {
+        Interfaces::new(self)
+    }
+
+    pub fn get_nested_types(&self) -> NestedTypes<'d> {
+        NestedTypes::new(self)
+    }
+
+    /// The class this one is nested in
+    pub fn get_nesting_type(&self) -> Option<MonoClass<'d>> {
+        let ptr = unsafe { sys::mono_class_get_nesting_type(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(MonoClass::new)
+    }
+
+    /// The `System.Reflection.TypeAttributes` of the class
+    pub fn get_flags(&self) -> u32 {
+        unsafe { sys::mono_class_get_flags(self.raw.as_ptr()) }
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.get_flags() & sys::MONO_TYPE_ATTR_ABSTRACT != 0
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.get_flags() & sys::MONO_TYPE_ATTR_SEALED != 0
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.get_flags() & sys::MONO_TYPE_ATTR_CLASS_SEMANTIC_MASK == sys::MONO_TYPE_ATTR_INTERFACE
+    }
+
+    pub fn is_valuetype(&self) -> bool {
+        unsafe { sys::mono_class_is_valuetype(self.raw.as_ptr()) != 0 }
+    }
+
+    pub fn is_enum(&self) -> bool {
+        unsafe { sys::mono_class_is_enum(self.raw.as_ptr()) != 0 }
+    }
+
+    pub fn is_delegate(&self) -> bool {
+        unsafe { sys::mono_class_is_delegate(self.raw.as_ptr()) != 0 }
+    }
+
+    /// Whether this is an open generic type like ``List`1``, as opposed to ``List`1[int]``
+    ///
+    /// Mono has no embedding function for this, so it goes through `System.Type`
+    /// and needs the current domain
+    pub fn is_generic_definition(&self) -> bool {
+        let domain = match Domain::current() {
+            Some(domain) => domain,
+            None => return false,
+        };
+        let ty = unsafe {
+            sys::mono_type_get_object(
+                domain.raw.as_ptr(),
+                sys::mono_class_get_type(self.raw.as_ptr()),
+            )
+        };
+        let ty = MonoObject::new(ty as *mut _);
+        // looked up on the runtime class of the type object, which overrides the property
+        ty.get_class()
+            .get_property_from_name("IsGenericTypeDefinition")
+            .and_then(|property| property.get::<bool>(&ty).ok())
+            .unwrap_or(false)
+    }
+
+    /// Number of dimensions of an array class, 0 for anything else
+    pub fn get_rank(&self) -> u32 {
+        unsafe { sys::mono_class_get_rank(self.raw.as_ptr()) as u32 }
+    }
+
+    /// The class of the elements for arrays and pointers, the underlying type for enums
+    /// and the class itself otherwise
+    pub fn get_element_class(&self) -> MonoClass<'d> {
+        let ptr = unsafe { sys::mono_class_get_element_class(self.raw.as_ptr()) };
+        MonoClass::new(NonNull::new(ptr).unwrap())
+    }
+
+    /// Whether this class derives from `other`, or is `other` itself.
+    /// With `check_interfaces` implementing the interface `other` counts as well
+    pub fn is_subclass_of(&self, other: &MonoClass<'_>, check_interfaces: bool) -> bool {
+        unsafe {
+            sys::mono_class_is_subclass_of(
+                self.raw.as_ptr(),
+                other.raw.as_ptr(),
+                check_interfaces as _,
+            ) != 0
+        }
+    }
+
+    /// Whether instances of `other` can be stored in a location of this class
+    pub fn is_assignable_from(&self, other: &MonoClass<'_>) -> bool {
+        unsafe { sys::mono_class_is_assignable_from(self.raw.as_ptr(), other.raw.as_ptr()) != 0 }
     }
 }
 
 #[non_exhaustive]
 #[repr(transparent)]
 pub struct Method<'d> {
-    raw: NonNull<sys::MonoMethod>,
+    pub(crate) raw: NonNull<sys::MonoMethod>,
     _m: PhantomData<&'d Domain>,
 }
 
@@ -86,42 +249,112 @@ impl<'d> Method<'d> {
 
     /// this   - the 'this' ptr <br/>
     /// params - arguments <br/>
-    /// exc    - exception information <br/>
     pub unsafe fn invoke(
         &self,
         // this: *mut c_void,
-        this: Option<&MonoObject>,
+        this: Option<&MonoObject<'_>>,
         params: *mut *mut c_void,
-        exc: *mut *mut sys::MonoObject,
-    ) -> MonoObject {
+    ) -> Result<MonoObject<'d>, MonoException> {
+        let mut exc = null_mut();
         let ptr = sys::mono_runtime_invoke(
             self.raw.as_ptr(),
-            this.map_or(null_mut(), |s| s.ptr as *mut _),
+            this.map_or(null_mut(), this_ptr),
             params,
-            exc,
+            &mut exc,
         );
-        MonoObject::new(ptr)
+        exception::check(MonoObject::new(ptr), exc)
     }
 
     pub unsafe fn invoke_array(
         &self,
         this: *mut c_void,
         params: *mut sys::MonoArray,
-        exc: *mut *mut sys::MonoObject,
-    ) -> MonoObject {
-        let ptr = sys::mono_runtime_invoke_array(self.raw.as_ptr(), this, params, exc);
-        MonoObject::new(ptr)
+    ) -> Result<MonoObject<'d>, MonoException> {
+        let mut exc = null_mut();
+        let ptr = sys::mono_runtime_invoke_array(self.raw.as_ptr(), this, params, &mut exc);
+        exception::check(MonoObject::new(ptr), exc)
     }
 
-    pub fn get_name(&self) -> String {
-        unsafe {
-            let char_ptr = sys::mono_method_get_name(self.raw.as_ptr()) as *mut _;
-            let cstr = CString::from_raw(char_ptr);
-            cstr.to_str().unwrap().to_string()
+    /// Calls the method with a tuple of arguments, checking them against the signature first,
+    /// a thrown managed exception is returned as a [`MonoException`] error
+    ///
+    /// `this` is required for instance methods and ignored for static ones
+    pub fn call<A: MethodArgs>(
+        &self,
+        this: Option<&MonoObject<'_>>,
+        args: A,
+    ) -> Result<MonoObject<'d>> {
+        let domain = Domain::current().ok_or_else(|| anyhow!("No domain loaded"))?;
+        let args = args.to_mono_args(&domain)?;
+        self.call_objects(this, &args)
+    }
+
+    /// [`call`](Self::call) with arguments that have already been converted
+    pub(crate) fn call_objects(
+        &self,
+        this: Option<&MonoObject<'_>>,
+        args: &[MonoObject<'_>],
+    ) -> Result<MonoObject<'d>> {
+        let domain = Domain::current().ok_or_else(|| anyhow!("No domain loaded"))?;
+        let signature = self.signature();
+
+        let params = signature.params();
+        if params.len() != args.len() {
+            return Err(anyhow!(
+                "{} takes {} arguments but {} were given",
+                self.get_name(),
+                params.len(),
+                args.len()
+            ));
+        }
+
+        let this = if signature.is_instance() {
+            let this = this
+                .filter(|this| !this.is_null())
+                .ok_or_else(|| anyhow!("{} needs a this object", self.get_name()))?;
+            this_ptr(this)
+        } else {
+            null_mut()
+        };
+
+        let object_class =
+            MonoClass::new(NonNull::new(unsafe { sys::mono_get_object_class() }).unwrap());
+        let array = domain.create_array(&object_class, args.len());
+        for (i, (param, arg)) in params.iter().zip(args.iter()).enumerate() {
+            if !param.accepts(arg) {
+                return Err(anyhow!(
+                    "Argument {} of {} should be {}",
+                    i,
+                    self.get_name(),
+                    param.name()
+                ));
+            }
+            array.set_ref(i, arg);
         }
+
+        Ok(unsafe { self.invoke_array(this, array.ptr)? })
     }
 
-    pub fn signature(&self) -> MethodSignature {
+    /// Like [`call`](Self::call) but converts the return value,
+    /// use `()` for methods returning void
+    pub fn call_as<R: FromMono<'d>, A: MethodArgs>(
+        &self,
+        this: Option<&MonoObject<'_>>,
+        args: A,
+    ) -> Result<R> {
+        R::from_mono(&self.call(this, args)?)
+    }
+
+    pub fn get_name(&self) -> String {
+        self.get_name_cstr().to_string_lossy().into_owned()
+    }
+
+    /// The name as mono stores it, without a copy
+    pub fn get_name_cstr(&self) -> &'d CStr {
+        unsafe { CStr::from_ptr(sys::mono_method_get_name(self.raw.as_ptr())) }
+    }
+
+    pub fn signature(&self) -> MethodSignature<'d> {
         MethodSignature::new(self)
     }
 }
@@ -139,7 +372,7 @@ impl PartialEq for MethodSignature<'_> {
     }
 }
 
-impl MethodSignature<'_> {
+impl<'d> MethodSignature<'d> {
     pub(crate) fn new_(raw: NonNull<sys::MonoMethodSignature>) -> Self {
         Self {
             raw,
@@ -147,7 +380,7 @@ impl MethodSignature<'_> {
         }
     }
 
-    pub fn new(method: &Method) -> Self {
+    pub fn new(method: &Method<'d>) -> Self {
         let ptr = unsafe { sys::mono_method_signature(method.raw.as_ptr()) };
         let raw = NonNull::new(ptr).unwrap();
         Self::new_(raw)
@@ -156,45 +389,368 @@ impl MethodSignature<'_> {
     pub fn param_count(&self) -> u32 {
         unsafe { sys::mono_signature_get_param_count(self.raw.as_ptr()) }
     }
+
+    pub fn params(&self) -> Vec<MonoType<'d>> {
+        let mut params = Vec::new();
+        let mut iter = null_mut();
+        loop {
+            let ptr = unsafe { sys::mono_signature_get_params(self.raw.as_ptr(), &mut iter) };
+            match NonNull::new(ptr) {
+                Some(raw) => params.push(MonoType::new(raw)),
+                None => break,
+            }
+        }
+        params
+    }
+
+    pub fn return_type(&self) -> MonoType<'d> {
+        let ptr = unsafe { sys::mono_signature_get_return_type(self.raw.as_ptr()) };
+        let raw = NonNull::new(ptr).unwrap();
+        MonoType::new(raw)
+    }
+
+    /// Whether the method takes a this object
+    pub fn is_instance(&self) -> bool {
+        unsafe { sys::mono_signature_is_instance(self.raw.as_ptr()) != 0 }
+    }
+}
+
+/// A parsed method description like `App.Program:PrintNumber(uint,uint)`
+///
+/// The class may leave out the namespace, and the parameter list can be left out to match
+/// any overload. Parameter types use the C# names of builtin types, e.g. `int` or `string`.
+pub struct MethodDesc {
+    raw: NonNull<sys::MonoMethodDesc>,
+}
+
+impl MethodDesc {
+    pub fn new(desc: &str) -> Result<Self> {
+        let cstr = CString::new(desc)?;
+        let ptr = unsafe { sys::mono_method_desc_new(cstr.as_ptr(), 1) };
+        let raw =
+            NonNull::new(ptr).ok_or_else(|| anyhow!("Invalid method description {}", desc))?;
+        Ok(Self { raw })
+    }
+
+    /// Searches the methods declared by `class`
+    pub fn search_in_class<'d>(&self, class: &MonoClass<'d>) -> Option<Method<'d>> {
+        let ptr =
+            unsafe { sys::mono_method_desc_search_in_class(self.raw.as_ptr(), class.raw.as_ptr()) };
+        NonNull::new(ptr).map(Method::new)
+    }
+
+    /// Searches every class of `image`, which needs the class in the description
+    pub fn search_in_image<'d>(&self, image: &Image<'d>) -> Option<Method<'d>> {
+        let ptr =
+            unsafe { sys::mono_method_desc_search_in_image(self.raw.as_ptr(), image.raw.as_ptr()) };
+        NonNull::new(ptr).map(Method::new)
+    }
+
+    /// Whether `method` fits the description, including its class if the description has one
+    pub fn matches(&self, method: &Method<'_>) -> bool {
+        unsafe {
+            if sys::mono_method_desc_is_full(self.raw.as_ptr()) != 0 {
+                sys::mono_method_desc_full_match(self.raw.as_ptr(), method.raw.as_ptr()) != 0
+            } else {
+                sys::mono_method_desc_match(self.raw.as_ptr(), method.raw.as_ptr()) != 0
+            }
+        }
+    }
+}
+
+impl Drop for MethodDesc {
+    fn drop(&mut self) {
+        unsafe { sys::mono_method_desc_free(self.raw.as_ptr()) }
+    }
 }
 
 #[repr(transparent)]
-pub struct MonoClassField {
+pub struct MonoClassField<'d> {
     pub(crate) raw: NonNull<sys::MonoClassField>,
+    _m: PhantomData<&'d Domain>,
 }
 
-impl MonoClassField {
-    pub fn get_type(&self) -> MonoType {
+impl<'d> MonoClassField<'d> {
+    pub(crate) fn new(raw: NonNull<sys::MonoClassField>) -> Self {
+        Self {
+            raw,
+            _m: PhantomData,
+        }
+    }
+
+    pub fn get_type(&self) -> MonoType<'d> {
         let ptr = unsafe { sys::mono_field_get_type(self.raw.as_ptr()) };
         let raw = NonNull::new(ptr).unwrap(); // should not fail
-        MonoType { raw }
+        MonoType::new(raw)
+    }
+
+    pub fn get_name(&self) -> String {
+        let cstr = unsafe { CStr::from_ptr(sys::mono_field_get_name(self.raw.as_ptr())) };
+        cstr.to_string_lossy().into_owned()
+    }
+
+    /// The class declaring the field
+    pub fn get_parent(&self) -> MonoClass<'d> {
+        let ptr = unsafe { sys::mono_field_get_parent(self.raw.as_ptr()) };
+        MonoClass::new(NonNull::new(ptr).unwrap())
+    }
+
+    pub fn is_static(&self) -> bool {
+        unsafe { sys::mono_field_get_flags(self.raw.as_ptr()) & sys::MONO_FIELD_ATTR_STATIC != 0 }
+    }
+
+    /// Reads the field of `obj`, which has to be an instance of the declaring class
+    pub fn get<T: FromMono<'d>>(&self, obj: &MonoObject<'d>) -> Result<T> {
+        self.expect_instance_field(obj)?;
+        T::from_mono(&convert::get_field_value(obj, self))
     }
 
-    // pub unsafe fn get_data(&self) -> *const i8 {
-    //     sys::mono_field_get_data(self.raw.as_ptr())
-    // }
+    /// Writes the field of `obj`, `value` has to fit the type of the field
+    pub fn set<T: ToMono>(&self, obj: &MonoObject<'_>, value: T) -> Result<()> {
+        self.expect_instance_field(obj)?;
+        let domain = Domain::borrowed(unsafe { sys::mono_object_get_domain(obj.ptr) })
+            .ok_or_else(|| anyhow!("No domain loaded"))?;
+        convert::set_field_value(obj, self, value.to_mono(&domain)?, &domain)
+    }
+
+    /// Reads a static field, every domain has its own copy
+    pub fn get_static<T: FromMono<'d>>(&self, domain: &'d Domain) -> Result<T> {
+        self.expect_static_field()?;
+        T::from_mono(&convert::get_static_value(self, domain)?)
+    }
+
+    /// Writes a static field in `domain`, `value` has to fit the type of the field
+    pub fn set_static<T: ToMono>(&self, domain: &Domain, value: T) -> Result<()> {
+        self.expect_static_field()?;
+        convert::set_static_value(self, value.to_mono(domain)?, domain)
+    }
+
+    fn expect_instance_field(&self, obj: &MonoObject<'_>) -> Result<()> {
+        if self.is_static() {
+            return Err(anyhow!("{} is static", self.get_name()));
+        }
+        convert::expect_instance(obj, &self.get_parent())
+    }
+
+    fn expect_static_field(&self) -> Result<()> {
+        if !self.is_static() {
+            return Err(anyhow!("{} is not static", self.get_name()));
+        }
+        Ok(())
+    }
+}
+
+#[non_exhaustive]
+#[repr(transparent)]
+pub struct MonoProperty<'d> {
+    pub(crate) raw: NonNull<sys::MonoProperty>,
+    _m: PhantomData<&'d Domain>,
+}
 
-    // pub unsafe fn get_data_mut(&self) -> *mut i8 {
-    //     sys::mono_field_get_data(self.raw.as_ptr()) as *mut i8
-    // }
+impl<'d> MonoProperty<'d> {
+    pub(crate) fn new(raw: NonNull<sys::MonoProperty>) -> Self {
+        Self {
+            raw,
+            _m: PhantomData,
+        }
+    }
 
-    // pub unsafe fn cast<T>(&self) -> *const T {
-    //     self.get_data() as *const T
-    // }
+    pub fn get_name(&self) -> String {
+        let cstr = unsafe { CStr::from_ptr(sys::mono_property_get_name(self.raw.as_ptr())) };
+        cstr.to_string_lossy().into_owned()
+    }
 
-    // pub unsafe fn cast_mut<T>(&mut self) -> *mut T {
-    //     self.get_data_mut() as *mut T
-    // }
+    /// The class declaring the property
+    pub fn get_parent(&self) -> MonoClass<'d> {
+        let ptr = unsafe { sys::mono_property_get_parent(self.raw.as_ptr()) };
+        MonoClass::new(NonNull::new(ptr).unwrap())
+    }
 
-    // pub unsafe fn cast_deref<T>(&self) -> &T {
-    //     &*self.cast()
-    // }
+    /// The `System.Reflection.PropertyAttributes` of the property
+    pub fn get_flags(&self) -> u32 {
+        unsafe { sys::mono_property_get_flags(self.raw.as_ptr()) }
+    }
 
-    // pub unsafe fn cast_deref_mut<T>(&mut self) -> &mut T {
-    //     &mut *self.cast_mut()
-    // }
+    pub fn get_method(&self) -> Option<Method<'d>> {
+        let ptr = unsafe { sys::mono_property_get_get_method(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(Method::new)
+    }
+
+    pub fn set_method(&self) -> Option<Method<'d>> {
+        let ptr = unsafe { sys::mono_property_get_set_method(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(Method::new)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.get_method()
+            .or_else(|| self.set_method())
+            .is_some_and(|method| {
+                let flags = unsafe { sys::mono_method_get_flags(method.raw.as_ptr(), null_mut()) };
+                flags & sys::MONO_METHOD_ATTR_STATIC != 0
+            })
+    }
+
+    /// Instances of the attributes applied to the property
+    pub fn custom_attributes(&self) -> Result<Vec<MonoObject<'d>>> {
+        let info = unsafe {
+            sys::mono_custom_attrs_from_property(self.get_parent().raw.as_ptr(), self.raw.as_ptr())
+        };
+        custom_attributes(info)
+    }
+
+    /// Whether an attribute of class `attribute`, or of a subclass of it, is applied to the property
+    pub fn has_attribute(&self, attribute: &MonoClass<'_>) -> bool {
+        let info = unsafe {
+            sys::mono_custom_attrs_from_property(self.get_parent().raw.as_ptr(), self.raw.as_ptr())
+        };
+        has_attribute(info, attribute)
+    }
+
+    /// Runs the getter on `obj`, which has to be an instance of the declaring class,
+    /// a thrown managed exception is returned as a [`MonoException`] error
+    pub fn get<T: FromMono<'d>>(&self, obj: &MonoObject<'d>) -> Result<T> {
+        self.expect_instance_property(obj)?;
+        T::from_mono(&self.get_value(this_ptr(obj))?)
+    }
+
+    /// Runs the setter on `obj`, `value` has to fit the type of the property
+    pub fn set<T: ToMono>(&self, obj: &MonoObject<'_>, value: T) -> Result<()> {
+        self.expect_instance_property(obj)?;
+        let domain = Domain::borrowed(unsafe { sys::mono_object_get_domain(obj.ptr) })
+            .ok_or_else(|| anyhow!("No domain loaded"))?;
+        self.set_value(this_ptr(obj), value.to_mono(&domain)?, &domain)
+    }
+
+    /// Runs the getter of a static property, `domain` has to be the current domain
+    pub fn get_static<T: FromMono<'d>>(&self, domain: &'d Domain) -> Result<T> {
+        self.expect_static_property(domain)?;
+        T::from_mono(&self.get_value(null_mut())?)
+    }
+
+    /// Runs the setter of a static property, `domain` has to be the current domain
+    pub fn set_static<T: ToMono>(&self, domain: &Domain, value: T) -> Result<()> {
+        self.expect_static_property(domain)?;
+        self.set_value(null_mut(), value.to_mono(domain)?, domain)
+    }
+
+    fn get_value(&self, this: *mut c_void) -> Result<MonoObject<'d>> {
+        let getter = self
+            .get_method()
+            .ok_or_else(|| anyhow!("{} has no getter", self.get_name()))?;
+        if getter.signature().param_count() != 0 {
+            return Err(anyhow!("{} is an indexer", self.get_name()));
+        }
+
+        let mut exc = null_mut();
+        let ptr =
+            unsafe { sys::mono_property_get_value(self.raw.as_ptr(), this, null_mut(), &mut exc) };
+        Ok(exception::check(MonoObject::new(ptr), exc)?)
+    }
+
+    fn set_value(&self, this: *mut c_void, value: MonoObject<'_>, domain: &Domain) -> Result<()> {
+        let setter = self
+            .set_method()
+            .ok_or_else(|| anyhow!("{} has no setter", self.get_name()))?;
+        let params = setter.signature().params();
+        let ty = match params.as_slice() {
+            [ty] => ty,
+            _ => return Err(anyhow!("{} is an indexer", self.get_name())),
+        };
+        if !ty.accepts(&value) {
+            return Err(anyhow!("Property should be {}", ty.name()));
+        }
+
+        let class = ty
+            .get_class()
+            .ok_or_else(|| anyhow!("{} has no class", ty.name()))?;
+        let (_value, ptr) = convert::to_native(value, &class, domain)?;
+        let mut params = [ptr];
+        let mut exc = null_mut();
+        unsafe {
+            sys::mono_property_set_value(self.raw.as_ptr(), this, params.as_mut_ptr(), &mut exc)
+        };
+        Ok(exception::check((), exc)?)
+    }
+
+    fn expect_instance_property(&self, obj: &MonoObject<'_>) -> Result<()> {
+        if self.is_static() {
+            return Err(anyhow!("{} is static", self.get_name()));
+        }
+        convert::expect_instance(obj, &self.get_parent())
+    }
+
+    fn expect_static_property(&self, domain: &Domain) -> Result<()> {
+        if !self.is_static() {
+            return Err(anyhow!("{} is not static", self.get_name()));
+        }
+        if Domain::current().as_ref() != Some(domain) {
+            return Err(anyhow!(
+                "{} is not the current domain",
+                domain.friendly_name()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Methods of value types take a pointer to the unboxed value as `this`
+fn this_ptr(obj: &MonoObject<'_>) -> *mut c_void {
+    unsafe {
+        if sys::mono_class_is_valuetype(sys::mono_object_get_class(obj.ptr)) != 0 {
+            obj.unbox()
+        } else {
+            obj.ptr as *mut c_void
+        }
+    }
+}
+
+/// Constructs the attributes of `info` and frees it, null means there are none
+fn custom_attributes<'d>(info: *mut sys::MonoCustomAttrInfo) -> Result<Vec<MonoObject<'d>>> {
+    if info.is_null() {
+        return Ok(Vec::new());
+    }
+    let array = unsafe { sys::mono_custom_attrs_construct(info) };
+    unsafe { sys::mono_custom_attrs_free(info) };
+    Vec::from_mono(&MonoObject::new(array as *mut _))
+}
+
+/// Checks `info` for an attribute and frees it, null means there are none
+fn has_attribute(info: *mut sys::MonoCustomAttrInfo, attribute: &MonoClass<'_>) -> bool {
+    if info.is_null() {
+        return false;
+    }
+    unsafe {
+        let found = sys::mono_custom_attrs_has_attr(info, attribute.raw.as_ptr()) != 0;
+        sys::mono_custom_attrs_free(info);
+        found
+    }
+}
+
+/// Looks up a class of corlib
+pub(crate) fn corlib_class<'d>(namespace: &str, name: &str) -> Result<MonoClass<'d>> {
+    let namespace_cstr = CString::new(namespace)?;
+    let name_cstr = CString::new(name)?;
+    let ptr = unsafe {
+        sys::mono_class_from_name(
+            sys::mono_get_corlib(),
+            namespace_cstr.as_ptr(),
+            name_cstr.as_ptr(),
+        )
+    };
+    NonNull::new(ptr)
+        .map(MonoClass::new)
+        .ok_or_else(|| anyhow!("{}.{} not found in corlib", namespace, name))
+}
 
-    // pub unsafe fn as_obj(&mut self) -> MonoObject {
-    //     MonoObject::new(self.cast_mut())
-    // }
+/// Looks up a method of corlib by its description, e.g. `System.Object:ToString()`
+pub(crate) fn corlib_method<'d>(desc: &str) -> Result<Method<'d>> {
+    let ptr = unsafe {
+        sys::mono_method_desc_search_in_image(
+            MethodDesc::new(desc)?.raw.as_ptr(),
+            sys::mono_get_corlib(),
+        )
+    };
+    NonNull::new(ptr)
+        .map(Method::new)
+        .ok_or_else(|| anyhow!("{} not found in corlib", desc))
 }
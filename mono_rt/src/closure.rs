@@ -0,0 +1,476 @@
+//! Rust closures as internal calls and as managed delegates
+//!
+//! An internal call is resolved by name to a single function pointer, so every closure type
+//! gets its own `extern "C"` shim which finds the closure in a registry keyed by its type.
+//!
+//! Delegates need a managed method to point at, one class with an internal call `Invoke`
+//! matching the delegate is emitted per delegate type and closure type.
+//! Each delegate targets its own instance of that class, which holds the boxed closure.
+
+use crate::class::{corlib_class, corlib_method, Method};
+use crate::icall::{self, IcallArg, IcallReturn};
+use crate::{Domain, MonoClass, MonoDelegate, MonoObject};
+use anyhow::{anyhow, Result};
+use std::any::{Any, TypeId};
+use std::ffi::c_void;
+use std::ptr::{null_mut, NonNull};
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
+
+/// Closures registered as internal calls
+static ICALLS: Mutex<Vec<IcallClosure>> = Mutex::new(Vec::new());
+/// Classes emitted to back delegates
+static DELEGATE_CLASSES: Mutex<Vec<DelegateClass>> = Mutex::new(Vec::new());
+/// Frees the closure of a delegate once its target has been collected
+static REFERENCE_QUEUE: AtomicPtr<sys::MonoReferenceQueue> = AtomicPtr::new(null_mut());
+static EMITTED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+const CREATE_DELEGATE: &str =
+    "System.Delegate:CreateDelegate(System.Type,object,System.Reflection.MethodInfo)";
+const GET_CURRENT_DOMAIN: &str = "System.AppDomain:get_CurrentDomain()";
+const ASSEMBLY_NAME_CTOR: &str = "System.Reflection.AssemblyName:.ctor(string)";
+const DEFINE_DYNAMIC_ASSEMBLY: &str = "System.AppDomain:DefineDynamicAssembly(System.Reflection.AssemblyName,System.Reflection.Emit.AssemblyBuilderAccess)";
+const DEFINE_DYNAMIC_MODULE: &str =
+    "System.Reflection.Emit.AssemblyBuilder:DefineDynamicModule(string)";
+const DEFINE_TYPE: &str =
+    "System.Reflection.Emit.ModuleBuilder:DefineType(string,System.Reflection.TypeAttributes)";
+const DEFINE_FIELD: &str = "System.Reflection.Emit.TypeBuilder:DefineField(string,System.Type,System.Reflection.FieldAttributes)";
+const DEFINE_METHOD: &str = "System.Reflection.Emit.TypeBuilder:DefineMethod(string,System.Reflection.MethodAttributes,System.Type,System.Type[])";
+const SET_IMPLEMENTATION_FLAGS: &str = "System.Reflection.Emit.MethodBuilder:SetImplementationFlags(System.Reflection.MethodImplAttributes)";
+const CREATE_TYPE: &str = "System.Reflection.Emit.TypeBuilder:CreateType()";
+
+struct IcallClosure {
+    type_id: TypeId,
+    path: String,
+    domain: usize,
+    closure: Arc<dyn Any + Send + Sync>,
+}
+
+struct DelegateClass {
+    domain: usize,
+    delegate: usize,
+    type_id: TypeId,
+    class: NonNull<sys::MonoClass>,
+}
+
+// Emitted classes are never unloaded before their domain
+unsafe impl Send for DelegateClass {}
+
+/// What the target of a closure delegate points to
+type DelegateState = Box<dyn Any + Send + Sync>;
+
+impl Domain {
+    /// Registers a closure as an internal call, it is dropped along with the domain
+    ///
+    /// Closures implementing `Fn` can be passed as is, `FnMut` closures have to be wrapped
+    /// in a [`Mutex`]. Calling such a closure while it is already running, from the managed
+    /// code it calls or from another thread, throws instead of waiting.
+    ///
+    /// Every closure type backs at most one internal call, registering the same closure type
+    /// again for the same path replaces the previous closure.
+    pub fn add_internal_call_closure<Args, C: ClosureFn<Args>>(
+        &self,
+        path: &str,
+        closure: C,
+    ) -> Result<()> {
+        let type_id = TypeId::of::<C>();
+        let domain = self.raw.as_ptr() as usize;
+        {
+            let mut icalls = ICALLS.lock().unwrap();
+            if let Some(other) = icalls
+                .iter()
+                .find(|i| i.type_id == type_id && i.path != path)
+            {
+                return Err(anyhow!(
+                    "This closure type is already registered as {}",
+                    other.path
+                ));
+            }
+            icalls.retain(|i| i.type_id != type_id);
+            icalls.push(IcallClosure {
+                type_id,
+                path: path.to_string(),
+                domain,
+                closure: Arc::new(closure),
+            });
+        }
+        unsafe { self.add_internal_call_raw(path, C::icall_shim()) }
+    }
+
+    /// Creates a delegate of the given delegate type calling a closure,
+    /// the closure is dropped once the delegate has been collected
+    ///
+    /// Arguments and the return value are converted as for an internal call, the closure has
+    /// to take the parameters of the delegate's `Invoke` method. `FnMut` closures have to be
+    /// wrapped in a [`Mutex`], see [`add_internal_call_closure`](Self::add_internal_call_closure).
+    pub fn create_delegate<Args, C: ClosureFn<Args>>(
+        &self,
+        delegate: &MonoClass<'_>,
+        closure: C,
+    ) -> Result<MonoDelegate<'_>> {
+        if !delegate.is_delegate() {
+            return Err(anyhow!("{} is not a delegate type", delegate.get_name()));
+        }
+        let invoke = unsafe {
+            NonNull::new(sys::mono_get_delegate_invoke(delegate.raw.as_ptr()))
+                .map(Method::new)
+                .ok_or_else(|| anyhow!("{} has no Invoke method", delegate.get_name()))?
+        };
+        if invoke.signature().param_count() as usize != C::ARITY {
+            return Err(anyhow!(
+                "{} takes {} arguments but the closure takes {}",
+                delegate.get_name(),
+                invoke.signature().param_count(),
+                C::ARITY
+            ));
+        }
+
+        let class = self.delegate_class::<C, Args>(delegate, &invoke)?;
+        let target = self.create_object(&class);
+        let state: *mut DelegateState = Box::into_raw(Box::new(Box::new(closure)));
+        unsafe {
+            let field = sys::mono_class_get_field_from_name(
+                class.raw.as_ptr(),
+                "Handle\0".as_ptr() as *const _,
+            );
+            sys::mono_field_set_value(target.ptr, field, &state as *const _ as *mut c_void);
+            if sys::mono_gc_reference_queue_add(reference_queue(), target.ptr, state as *mut _) == 0
+            {
+                drop(Box::from_raw(state));
+                return Err(anyhow!("Failed to track the delegate target"));
+            }
+        }
+
+        let method = class
+            .get_method_from_name("Invoke", C::ARITY as i32)
+            .ok_or_else(|| anyhow!("Emitted class has no Invoke method"))?;
+        let method_info = unsafe {
+            sys::mono_method_get_object(self.raw.as_ptr(), method.raw.as_ptr(), class.raw.as_ptr())
+        };
+        corlib_method(CREATE_DELEGATE)?.call_as(
+            None,
+            (
+                self.type_object(delegate),
+                target,
+                MonoObject::new(method_info as *mut _),
+            ),
+        )
+    }
+
+    /// The emitted class backing delegates of type `delegate` that call a `C`
+    fn delegate_class<C: ClosureFn<Args>, Args>(
+        &self,
+        delegate: &MonoClass<'_>,
+        invoke: &Method<'_>,
+    ) -> Result<MonoClass<'_>> {
+        let domain = self.raw.as_ptr() as usize;
+        let type_id = TypeId::of::<C>();
+        let mut classes = DELEGATE_CLASSES.lock().unwrap();
+        if let Some(c) = classes.iter().find(|c| {
+            c.domain == domain
+                && c.delegate == delegate.raw.as_ptr() as usize
+                && c.type_id == type_id
+        }) {
+            return Ok(MonoClass::new(c.class));
+        }
+
+        let name = format!("Closure{}", EMITTED_COUNT.fetch_add(1, Ordering::SeqCst));
+        unsafe {
+            self.add_internal_call_raw(&format!("MonoRt.{}::Invoke", name), C::delegate_shim())?
+        };
+        let class = self.emit_class(&name, invoke)?;
+        classes.push(DelegateClass {
+            domain,
+            delegate: delegate.raw.as_ptr() as usize,
+            type_id,
+            class: class.raw,
+        });
+        Ok(class)
+    }
+
+    /// Emits `MonoRt.<name>` with an `IntPtr Handle` field and an internal call `Invoke`
+    /// taking the parameters of `invoke`
+    fn emit_class(&self, name: &str, invoke: &Method<'_>) -> Result<MonoClass<'_>> {
+        let full_name = format!("MonoRt.{}", name);
+        let current_domain = corlib_method(GET_CURRENT_DOMAIN)?.call(None, ())?;
+
+        let assembly_name = self.create_object(&corlib_class("System.Reflection", "AssemblyName")?);
+        corlib_method(ASSEMBLY_NAME_CTOR)?.call(Some(&assembly_name), (full_name.as_str(),))?;
+        // Run
+        let access = self.enum_value("System.Reflection.Emit", "AssemblyBuilderAccess", 1)?;
+        let assembly = corlib_method(DEFINE_DYNAMIC_ASSEMBLY)?
+            .call(Some(&current_domain), (assembly_name, access))?;
+        let module =
+            corlib_method(DEFINE_DYNAMIC_MODULE)?.call(Some(&assembly), (full_name.as_str(),))?;
+
+        // Public | Sealed
+        let attributes = self.enum_value("System.Reflection", "TypeAttributes", 0x101)?;
+        let type_builder =
+            corlib_method(DEFINE_TYPE)?.call(Some(&module), (full_name.as_str(), attributes))?;
+
+        let intptr = MonoClass::new(NonNull::new(unsafe { sys::mono_get_intptr_class() }).unwrap());
+        // Public
+        let attributes = self.enum_value("System.Reflection", "FieldAttributes", 0x6)?;
+        corlib_method(DEFINE_FIELD)?.call(
+            Some(&type_builder),
+            ("Handle", self.type_object(&intptr), attributes),
+        )?;
+
+        let signature = invoke.signature();
+        let params = signature.params();
+        let param_types = self.create_array(&corlib_class("System", "Type")?, params.len());
+        for (i, param) in params.iter().enumerate() {
+            let ty = unsafe { sys::mono_type_get_object(self.raw.as_ptr(), param.raw.as_ptr()) };
+            param_types.set_ref(i, &MonoObject::new(ty as *mut _));
+        }
+        let return_type = unsafe {
+            sys::mono_type_get_object(self.raw.as_ptr(), signature.return_type().raw.as_ptr())
+        };
+        // Public | HideBySig
+        let attributes = self.enum_value("System.Reflection", "MethodAttributes", 0x86)?;
+        let method_builder = corlib_method(DEFINE_METHOD)?.call(
+            Some(&type_builder),
+            (
+                "Invoke",
+                attributes,
+                MonoObject::new(return_type as *mut _),
+                param_types,
+            ),
+        )?;
+        // InternalCall
+        let flags = self.enum_value("System.Reflection", "MethodImplAttributes", 0x1000)?;
+        corlib_method(SET_IMPLEMENTATION_FLAGS)?.call(Some(&method_builder), (flags,))?;
+
+        let ty = corlib_method(CREATE_TYPE)?.call(Some(&type_builder), ())?;
+        unsafe {
+            let ty = sys::mono_reflection_type_get_type(ty.ptr as *mut _);
+            NonNull::new(sys::mono_class_from_mono_type(ty))
+                .map(MonoClass::new)
+                .ok_or_else(|| anyhow!("Failed to emit {}", full_name))
+        }
+    }
+
+    /// The `System.Type` of a class
+    fn type_object(&self, class: &MonoClass<'_>) -> MonoObject<'_> {
+        let ptr = unsafe {
+            sys::mono_type_get_object(
+                self.raw.as_ptr(),
+                sys::mono_class_get_type(class.raw.as_ptr()),
+            )
+        };
+        MonoObject::new(ptr as *mut _)
+    }
+
+    /// A boxed value of an `int` based corlib enum
+    fn enum_value(&self, namespace: &str, name: &str, mut value: i32) -> Result<MonoObject<'_>> {
+        let class = corlib_class(namespace, name)?;
+        Ok(unsafe { self.value_box(&class, &mut value as *mut i32 as *mut c_void) })
+    }
+}
+
+/// Drops the internal call closures and forgets the delegate classes of a domain
+pub(crate) fn drop_domain(domain: NonNull<sys::MonoDomain>) {
+    let domain = domain.as_ptr() as usize;
+    ICALLS.lock().unwrap().retain(|i| i.domain != domain);
+    DELEGATE_CLASSES
+        .lock()
+        .unwrap()
+        .retain(|c| c.domain != domain);
+}
+
+/// Drops the closures of every domain once the JIT has been cleaned up
+pub(crate) fn drop_all() {
+    ICALLS.lock().unwrap().clear();
+    DELEGATE_CLASSES.lock().unwrap().clear();
+}
+
+fn reference_queue() -> *mut sys::MonoReferenceQueue {
+    unsafe extern "C" fn free_state(state: *mut c_void) {
+        drop(Box::from_raw(state as *mut DelegateState));
+    }
+
+    let queue = REFERENCE_QUEUE.load(Ordering::SeqCst);
+    if !queue.is_null() {
+        return queue;
+    }
+    let queue = unsafe { sys::mono_gc_reference_queue_new(Some(free_state)) };
+    match REFERENCE_QUEUE.compare_exchange(null_mut(), queue, Ordering::SeqCst, Ordering::SeqCst) {
+        Ok(_) => queue,
+        Err(existing) => {
+            unsafe { sys::mono_gc_reference_queue_free(queue) };
+            existing
+        }
+    }
+}
+
+/// The closure registered as an internal call for the type `C`
+fn icall_closure<C: Any + Send + Sync>() -> Result<Arc<C>> {
+    let icalls = ICALLS.lock().unwrap();
+    let icall = icalls
+        .iter()
+        .find(|i| i.type_id == TypeId::of::<C>())
+        .ok_or_else(|| anyhow!("The closure of this internal call was dropped with its domain"))?;
+    Ok(Arc::downcast(icall.closure.clone()).unwrap())
+}
+
+/// The closure held by the target of a closure delegate
+///
+/// # Safety
+/// `this` has to be an instance of a class emitted for `C`
+unsafe fn delegate_closure<'a, C: Any>(this: *mut sys::MonoObject) -> Result<&'a C> {
+    let field = sys::mono_class_get_field_from_name(
+        sys::mono_object_get_class(this),
+        "Handle\0".as_ptr() as *const _,
+    );
+    let mut state: *const DelegateState = std::ptr::null();
+    sys::mono_field_get_value(this, field, &mut state as *mut _ as *mut c_void);
+    state
+        .as_ref()
+        .and_then(|state| state.downcast_ref())
+        .ok_or_else(|| anyhow!("Delegate target does not hold a closure"))
+}
+
+mod sealed {
+    pub trait Sealed<Args> {}
+}
+
+/// Implemented for `Fn` closures and for `FnMut` closures in a [`Mutex`],
+/// of up to 16 parameters
+pub trait ClosureFn<Args>: sealed::Sealed<Args> + Send + Sync + 'static {
+    #[doc(hidden)]
+    const ARITY: usize;
+
+    /// The `extern "C"` shim calling the closure registered for `Self`
+    #[doc(hidden)]
+    fn icall_shim() -> *const c_void;
+
+    /// The `extern "C"` shim calling the closure of a delegate target,
+    /// it takes the target as an extra first argument
+    #[doc(hidden)]
+    fn delegate_shim() -> *const c_void;
+}
+
+macro_rules! closure_fn {
+    ($arity:expr; $($arg:ident $value:ident),*) => {
+        impl<Func, Ret, $($arg),*> sealed::Sealed<($($arg,)*)> for Func where Func: Fn($($arg),*) -> Ret {}
+
+        impl<Func, Ret, $($arg),*> ClosureFn<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + Send + Sync + 'static,
+            Ret: IcallReturn,
+            $($arg: IcallArg,)*
+        {
+            const ARITY: usize = $arity;
+
+            fn icall_shim() -> *const c_void {
+                extern "C" fn shim<Func, Ret, $($arg),*>($($value: $arg::Abi),*) -> Ret::Abi
+                where
+                    Func: Fn($($arg),*) -> Ret + Send + Sync + 'static,
+                    Ret: IcallReturn,
+                    $($arg: IcallArg,)*
+                {
+                    icall::run(|| {
+                        let f = icall_closure::<Func>()?;
+                        Ok((*f)($($arg::from_abi($value)?),*))
+                    })
+                }
+
+                shim::<Func, Ret, $($arg),*> as *const c_void
+            }
+
+            fn delegate_shim() -> *const c_void {
+                extern "C" fn shim<Func, Ret, $($arg),*>(
+                    this: *mut sys::MonoObject,
+                    $($value: $arg::Abi),*
+                ) -> Ret::Abi
+                where
+                    Func: Fn($($arg),*) -> Ret + Send + Sync + 'static,
+                    Ret: IcallReturn,
+                    $($arg: IcallArg,)*
+                {
+                    icall::run(|| {
+                        let f = unsafe { delegate_closure::<Func>(this)? };
+                        Ok(f($($arg::from_abi($value)?),*))
+                    })
+                }
+
+                shim::<Func, Ret, $($arg),*> as *const c_void
+            }
+        }
+
+        impl<Func, Ret, $($arg),*> sealed::Sealed<($($arg,)*)> for Mutex<Func> where Func: FnMut($($arg),*) -> Ret {}
+
+        impl<Func, Ret, $($arg),*> ClosureFn<($($arg,)*)> for Mutex<Func>
+        where
+            Func: FnMut($($arg),*) -> Ret + Send + 'static,
+            Ret: IcallReturn,
+            $($arg: IcallArg,)*
+        {
+            const ARITY: usize = $arity;
+
+            fn icall_shim() -> *const c_void {
+                extern "C" fn shim<Func, Ret, $($arg),*>($($value: $arg::Abi),*) -> Ret::Abi
+                where
+                    Func: FnMut($($arg),*) -> Ret + Send + 'static,
+                    Ret: IcallReturn,
+                    $($arg: IcallArg,)*
+                {
+                    icall::run(|| {
+                        let f = icall_closure::<Mutex<Func>>()?;
+                        let mut f = lock(&f)?;
+                        Ok((*f)($($arg::from_abi($value)?),*))
+                    })
+                }
+
+                shim::<Func, Ret, $($arg),*> as *const c_void
+            }
+
+            fn delegate_shim() -> *const c_void {
+                extern "C" fn shim<Func, Ret, $($arg),*>(
+                    this: *mut sys::MonoObject,
+                    $($value: $arg::Abi),*
+                ) -> Ret::Abi
+                where
+                    Func: FnMut($($arg),*) -> Ret + Send + 'static,
+                    Ret: IcallReturn,
+                    $($arg: IcallArg,)*
+                {
+                    icall::run(|| {
+                        let f = unsafe { delegate_closure::<Mutex<Func>>(this)? };
+                        let mut f = lock(f)?;
+                        Ok((*f)($($arg::from_abi($value)?),*))
+                    })
+                }
+
+                shim::<Func, Ret, $($arg),*> as *const c_void
+            }
+        }
+    };
+}
+
+/// Locks a `FnMut` closure, failing instead of deadlocking when it is re-entered
+fn lock<F>(f: &Mutex<F>) -> Result<std::sync::MutexGuard<'_, F>> {
+    match f.try_lock() {
+        Ok(guard) => Ok(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => Err(anyhow!("Closure is already running")),
+    }
+}
+
+closure_fn!(0;);
+closure_fn!(1; A a);
+closure_fn!(2; A a, B b);
+closure_fn!(3; A a, B b, C c);
+closure_fn!(4; A a, B b, C c, D d);
+closure_fn!(5; A a, B b, C c, D d, E e);
+closure_fn!(6; A a, B b, C c, D d, E e, F f);
+closure_fn!(7; A a, B b, C c, D d, E e, F f, G g);
+closure_fn!(8; A a, B b, C c, D d, E e, F f, G g, H h);
+closure_fn!(9; A a, B b, C c, D d, E e, F f, G g, H h, I i);
+closure_fn!(10; A a, B b, C c, D d, E e, F f, G g, H h, I i, J j);
+closure_fn!(11; A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k);
+closure_fn!(12; A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+closure_fn!(13; A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m);
+closure_fn!(14; A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n);
+closure_fn!(15; A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o);
+closure_fn!(16; A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o, P p);
@@ -0,0 +1,721 @@
+use crate::class::corlib_class;
+use crate::{Domain, MonoArray, MonoClass, MonoClassField, MonoDelegate, MonoObject, MonoString};
+use anyhow::{anyhow, Result};
+use std::any::TypeId;
+use std::ffi::{c_void, CStr, CString};
+use std::ptr::{null_mut, NonNull};
+use std::sync::Mutex;
+
+/// A rust value that can be turned into a managed value
+pub trait ToMono {
+    /// The managed class values of this type are converted to
+    fn mono_class(domain: &Domain) -> Result<MonoClass<'_>>;
+
+    /// Value types are returned boxed, reference types as is
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>>;
+}
+
+/// A rust value that can be read out of a managed value,
+/// handles read out of `obj` keep its lifetime
+pub trait FromMono<'d>: Sized {
+    /// `obj` is boxed for value types, and may be null
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self>;
+}
+
+/// A rust struct that mirrors a managed value type byte for byte
+///
+/// # Safety
+/// The implementing type must be `#[repr(C)]` and have exactly the same
+/// size and field layout as the managed type `NAMESPACE.NAME`
+pub unsafe trait MonoStruct: Copy {
+    const NAMESPACE: &'static str;
+    const NAME: &'static str;
+}
+
+/// The class a rust type was resolved to in a domain, pointers stored as `usize` to be `Send`
+struct CachedClass {
+    type_id: TypeId,
+    domain: usize,
+    class: usize,
+}
+
+/// Classes of `MonoStruct` and derived types, validated once per domain
+static CLASS_CACHE: Mutex<Vec<CachedClass>> = Mutex::new(Vec::new());
+
+/// The class of `T` in `domain`, `resolve` only runs the first time
+pub(crate) fn cached_class<'d, T: 'static>(
+    domain: &'d Domain,
+    resolve: impl FnOnce() -> Result<MonoClass<'d>>,
+) -> Result<MonoClass<'d>> {
+    let type_id = TypeId::of::<T>();
+    let domain_ptr = domain.raw.as_ptr() as usize;
+    let cached = CLASS_CACHE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|c| c.type_id == type_id && c.domain == domain_ptr)
+        .map(|c| c.class);
+    if let Some(class) = cached {
+        return class_from_ptr(class as *mut sys::MonoClass);
+    }
+
+    // resolved without the lock held, field types may need to be resolved as well
+    let class = resolve()?;
+    CLASS_CACHE.lock().unwrap().push(CachedClass {
+        type_id,
+        domain: domain_ptr,
+        class: class.raw.as_ptr() as usize,
+    });
+    Ok(class)
+}
+
+/// Forgets the classes of a domain that is being unloaded
+pub(crate) fn drop_domain(domain: NonNull<sys::MonoDomain>) {
+    let domain = domain.as_ptr() as usize;
+    CLASS_CACHE.lock().unwrap().retain(|c| c.domain != domain);
+}
+
+/// Forgets the classes of every domain once the JIT has been cleaned up
+pub(crate) fn drop_all() {
+    CLASS_CACHE.lock().unwrap().clear();
+}
+
+/// The domain conversions look classes up in when they are not given one
+fn current_domain() -> Result<Domain> {
+    Domain::current().ok_or_else(|| anyhow!("No domain loaded"))
+}
+
+fn class_from_ptr<'d>(ptr: *mut sys::MonoClass) -> Result<MonoClass<'d>> {
+    let raw = NonNull::new(ptr).ok_or_else(|| anyhow!("Class could not be loaded"))?;
+    Ok(MonoClass::new(raw))
+}
+
+/// Full name of the class, including the assembly it is defined in
+fn assembly_qualified_name(class: &MonoClass<'_>) -> String {
+    unsafe {
+        let ptr = sys::mono_type_get_name_full(
+            class.get_type().raw.as_ptr(),
+            sys::MonoTypeNameFormat_MONO_TYPE_NAME_FORMAT_ASSEMBLY_QUALIFIED,
+        );
+        let name = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+        sys::mono_free(ptr as *mut _);
+        name
+    }
+}
+
+/// Instantiates a generic type definition of corlib, e.g. ``System.Nullable`1``
+fn generic_instance<'d>(definition: &str, args: &[MonoClass<'d>]) -> Result<MonoClass<'d>> {
+    let args = args
+        .iter()
+        .map(|class| format!("[{}]", assembly_qualified_name(class)))
+        .collect::<Vec<_>>()
+        .join(",");
+    let name = CString::new(format!("{}[{}]", definition, args))?;
+
+    unsafe {
+        // mono may modify the buffer while parsing it
+        let name = name.into_raw();
+        let ty = sys::mono_reflection_type_from_name(name, sys::mono_get_corlib());
+        drop(CString::from_raw(name));
+
+        if ty.is_null() {
+            return Err(anyhow!("Could not instantiate {}", definition));
+        }
+        class_from_ptr(sys::mono_class_from_mono_type(ty))
+    }
+}
+
+/// Checks that `obj` is a non null instance of `class`, or of an enum based on `class`
+pub(crate) fn expect_instance(obj: &MonoObject<'_>, class: &MonoClass<'_>) -> Result<()> {
+    if obj.is_null() {
+        return Err(anyhow!("Expected {} but got null", class.get_name()));
+    }
+    unsafe {
+        if !sys::mono_object_isinst(obj.ptr, class.raw.as_ptr()).is_null() {
+            return Ok(());
+        }
+        let obj_class = sys::mono_object_get_class(obj.ptr);
+        if sys::mono_class_is_enum(obj_class) != 0 {
+            let base = sys::mono_class_from_mono_type(sys::mono_class_enum_basetype(obj_class));
+            if base == class.raw.as_ptr() {
+                return Ok(());
+            }
+        }
+    }
+    Err(anyhow!(
+        "Expected {} but got {}",
+        class.get_name(),
+        obj.get_class().get_name()
+    ))
+}
+
+/// Finds the `has_value` flag and the value field of a `System.Nullable<T>`,
+/// which are named differently between corlib versions
+fn nullable_fields<'d>(class: &MonoClass<'d>) -> Result<(MonoClassField<'d>, MonoClassField<'d>)> {
+    let mut has_value = None;
+    let mut value = None;
+    for field in class.get_fields().filter(|field| !field.is_static()) {
+        match field.get_name().as_str() {
+            "has_value" | "hasValue" => has_value = Some(field),
+            _ => value = Some(field),
+        }
+    }
+    has_value
+        .zip(value)
+        .ok_or_else(|| anyhow!("Unexpected layout of {}", class.get_name()))
+}
+
+/// Puts `value` in the format mono expects when storing it in a location of `class`:
+/// a pointer to the data for value types and the object itself for reference types.
+/// The returned object owns the data and has to outlive the pointer
+pub(crate) fn to_native<'d>(
+    value: MonoObject<'d>,
+    class: &MonoClass<'_>,
+    domain: &'d Domain,
+) -> Result<(MonoObject<'d>, *mut c_void)> {
+    if !class.is_valuetype() {
+        let ptr = value.ptr as *mut c_void;
+        return Ok((value, ptr));
+    }
+
+    unsafe {
+        if sys::mono_class_is_nullable(class.raw.as_ptr()) != 0 {
+            // a fresh nullable is zeroed, which means it has no value
+            let nullable = domain.create_object(class);
+            if !value.is_null() {
+                let (has_value, inner) = nullable_fields(class)?;
+                let mut flag = true;
+                sys::mono_field_set_value(
+                    nullable.ptr,
+                    has_value.raw.as_ptr(),
+                    &mut flag as *mut bool as *mut c_void,
+                );
+                sys::mono_field_set_value(nullable.ptr, inner.raw.as_ptr(), value.unbox());
+            }
+            let ptr = nullable.unbox();
+            return Ok((nullable, ptr));
+        }
+
+        if value.is_null() {
+            return Err(anyhow!("{} can not be null", class.get_name()));
+        }
+        let ptr = value.unbox();
+        Ok((value, ptr))
+    }
+}
+
+/// Reads an instance field, value types are boxed
+pub(crate) fn get_field_value<'d>(
+    obj: &MonoObject<'d>,
+    field: &MonoClassField<'_>,
+) -> MonoObject<'d> {
+    unsafe {
+        let domain = sys::mono_object_get_domain(obj.ptr);
+        MonoObject::new(sys::mono_field_get_value_object(
+            domain,
+            field.raw.as_ptr(),
+            obj.ptr,
+        ))
+    }
+}
+
+/// Writes an instance field, checking `value` against the type of the field
+pub(crate) fn set_field_value(
+    obj: &MonoObject<'_>,
+    field: &MonoClassField<'_>,
+    value: MonoObject<'_>,
+    domain: &Domain,
+) -> Result<()> {
+    let ty = field.get_type();
+    if !ty.accepts(&value) {
+        return Err(anyhow!("Field should be {}", ty.name()));
+    }
+    let class = class_from_ptr(unsafe { sys::mono_class_from_mono_type(ty.raw.as_ptr()) })?;
+    let (_value, ptr) = to_native(value, &class, domain)?;
+    unsafe { sys::mono_field_set_value(obj.ptr, field.raw.as_ptr(), ptr) };
+    Ok(())
+}
+
+/// The vtable of the class declaring `field` in `domain`, holding its static data
+fn static_vtable(field: &MonoClassField<'_>, domain: &Domain) -> Result<*mut sys::MonoVTable> {
+    let class = unsafe { sys::mono_field_get_parent(field.raw.as_ptr()) };
+    let vtable = unsafe { sys::mono_class_vtable(domain.raw.as_ptr(), class) };
+    if vtable.is_null() {
+        return Err(anyhow!("Could not initialize {}", unsafe {
+            CStr::from_ptr(sys::mono_class_get_name(class)).to_string_lossy()
+        }));
+    }
+    Ok(vtable)
+}
+
+/// Reads a static field in `domain`, value types are boxed
+pub(crate) fn get_static_value<'d>(
+    field: &MonoClassField<'_>,
+    domain: &'d Domain,
+) -> Result<MonoObject<'d>> {
+    let vtable = static_vtable(field, domain)?;
+    let ty = field.get_type();
+    let class = class_from_ptr(unsafe { sys::mono_class_from_mono_type(ty.raw.as_ptr()) })?;
+
+    unsafe {
+        if !class.is_valuetype() {
+            let mut ptr: *mut sys::MonoObject = null_mut();
+            sys::mono_field_static_get_value(
+                vtable,
+                field.raw.as_ptr(),
+                &mut ptr as *mut _ as *mut c_void,
+            );
+            return Ok(MonoObject::new(ptr));
+        }
+
+        let boxed = domain.create_object(&class);
+        sys::mono_field_static_get_value(vtable, field.raw.as_ptr(), boxed.unbox());
+        if sys::mono_class_is_nullable(class.raw.as_ptr()) == 0 {
+            return Ok(boxed);
+        }
+
+        // a nullable without a value is null, otherwise its value is boxed on its own
+        let (has_value, inner) = nullable_fields(&class)?;
+        let mut flag = false;
+        sys::mono_field_get_value(
+            boxed.ptr,
+            has_value.raw.as_ptr(),
+            &mut flag as *mut bool as *mut c_void,
+        );
+        if flag {
+            Ok(get_field_value(&boxed, &inner))
+        } else {
+            Ok(MonoObject::new(null_mut()))
+        }
+    }
+}
+
+/// Writes a static field in `domain`, checking `value` against the type of the field
+pub(crate) fn set_static_value(
+    field: &MonoClassField<'_>,
+    value: MonoObject<'_>,
+    domain: &Domain,
+) -> Result<()> {
+    let vtable = static_vtable(field, domain)?;
+    let ty = field.get_type();
+    if !ty.accepts(&value) {
+        return Err(anyhow!("Field should be {}", ty.name()));
+    }
+    let class = class_from_ptr(unsafe { sys::mono_class_from_mono_type(ty.raw.as_ptr()) })?;
+    let (_value, ptr) = to_native(value, &class, domain)?;
+    unsafe { sys::mono_field_static_set_value(vtable, field.raw.as_ptr(), ptr) };
+    Ok(())
+}
+
+macro_rules! impl_primitive {
+    ($($t:ty => $class:ident),* $(,)?) => {
+        $(
+            impl ToMono for $t {
+                fn mono_class(_domain: &Domain) -> Result<MonoClass<'_>> {
+                    class_from_ptr(unsafe { sys::$class() })
+                }
+
+                fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+                    let class = Self::mono_class(domain)?;
+                    Ok(unsafe { domain.value_box(&class, self as *const $t as *mut c_void) })
+                }
+            }
+
+            impl<'d> FromMono<'d> for $t {
+                fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+                    expect_instance(obj, &<$t as ToMono>::mono_class(&current_domain()?)?)?;
+                    Ok(unsafe { *(obj.unbox() as *const $t) })
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive! {
+    bool => mono_get_boolean_class,
+    i8 => mono_get_sbyte_class,
+    u8 => mono_get_byte_class,
+    i16 => mono_get_int16_class,
+    u16 => mono_get_uint16_class,
+    i32 => mono_get_int32_class,
+    u32 => mono_get_uint32_class,
+    i64 => mono_get_int64_class,
+    u64 => mono_get_uint64_class,
+    isize => mono_get_intptr_class,
+    usize => mono_get_uintptr_class,
+    f32 => mono_get_single_class,
+    f64 => mono_get_double_class,
+}
+
+/// `System.Char` is a single utf-16 code unit, so only chars of the basic multilingual plane fit
+impl ToMono for char {
+    fn mono_class(_domain: &Domain) -> Result<MonoClass<'_>> {
+        class_from_ptr(unsafe { sys::mono_get_char_class() })
+    }
+
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+        let mut units = [0_u16; 2];
+        match self.encode_utf16(&mut units) {
+            [unit] => {
+                let class = Self::mono_class(domain)?;
+                Ok(unsafe { domain.value_box(&class, unit as *mut u16 as *mut c_void) })
+            }
+            _ => Err(anyhow!("{:?} does not fit in a System.Char", self)),
+        }
+    }
+}
+
+impl<'d> FromMono<'d> for char {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        expect_instance(obj, &<char as ToMono>::mono_class(&current_domain()?)?)?;
+        let unit = unsafe { *(obj.unbox() as *const u16) };
+        std::char::from_u32(unit as u32).ok_or_else(|| anyhow!("Unpaired surrogate {:#x}", unit))
+    }
+}
+
+impl<T: MonoStruct + 'static> ToMono for T {
+    fn mono_class(domain: &Domain) -> Result<MonoClass<'_>> {
+        cached_class::<T>(domain, || {
+            let class = crate::assembly::find_loaded_class(domain, T::NAMESPACE, T::NAME)
+                .ok_or_else(|| anyhow!("Could not find class {}.{}", T::NAMESPACE, T::NAME))?;
+
+            let size = class.get_type().size().size();
+            if size != std::mem::size_of::<T>() {
+                return Err(anyhow!(
+                    "{}.{} is {} bytes but the rust struct is {} bytes",
+                    T::NAMESPACE,
+                    T::NAME,
+                    size,
+                    std::mem::size_of::<T>()
+                ));
+            }
+
+            Ok(class)
+        })
+    }
+
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+        let class = Self::mono_class(domain)?;
+        Ok(unsafe { domain.value_box(&class, self as *const T as *mut c_void) })
+    }
+}
+
+impl<'d, T: MonoStruct + 'static> FromMono<'d> for T {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        expect_instance(obj, &T::mono_class(&current_domain()?)?)?;
+        Ok(unsafe { *(obj.unbox() as *const T) })
+    }
+}
+
+impl ToMono for &str {
+    fn mono_class(_domain: &Domain) -> Result<MonoClass<'_>> {
+        class_from_ptr(unsafe { sys::mono_get_string_class() })
+    }
+
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+        let str = domain.create_string(self)?;
+        Ok(MonoObject::new(str.ptr as *mut _))
+    }
+}
+
+impl ToMono for String {
+    fn mono_class(domain: &Domain) -> Result<MonoClass<'_>> {
+        <&str>::mono_class(domain)
+    }
+
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+        self.as_str().to_mono(domain)
+    }
+}
+
+impl<'d> FromMono<'d> for String {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        expect_instance(obj, &String::mono_class(&current_domain()?)?)?;
+        Ok(MonoString::new(obj.ptr as *mut _).to_string())
+    }
+}
+
+impl ToMono for MonoObject<'_> {
+    fn mono_class(_domain: &Domain) -> Result<MonoClass<'_>> {
+        class_from_ptr(unsafe { sys::mono_get_object_class() })
+    }
+
+    fn to_mono<'d>(&self, _domain: &'d Domain) -> Result<MonoObject<'d>> {
+        Ok(MonoObject::new(self.ptr))
+    }
+}
+
+impl<'d> FromMono<'d> for MonoObject<'d> {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        Ok(MonoObject::new(obj.ptr))
+    }
+}
+
+impl ToMono for MonoString<'_> {
+    fn mono_class(domain: &Domain) -> Result<MonoClass<'_>> {
+        <&str>::mono_class(domain)
+    }
+
+    fn to_mono<'d>(&self, _domain: &'d Domain) -> Result<MonoObject<'d>> {
+        Ok(MonoObject::new(self.ptr as *mut _))
+    }
+}
+
+/// A null object turns into a null string
+impl<'d> FromMono<'d> for MonoString<'d> {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        if !obj.is_null() {
+            expect_instance(obj, &MonoString::mono_class(&current_domain()?)?)?;
+        }
+        Ok(MonoString::new(obj.ptr as *mut _))
+    }
+}
+
+impl ToMono for MonoArray<'_> {
+    fn mono_class(_domain: &Domain) -> Result<MonoClass<'_>> {
+        class_from_ptr(unsafe { sys::mono_get_array_class() })
+    }
+
+    fn to_mono<'d>(&self, _domain: &'d Domain) -> Result<MonoObject<'d>> {
+        Ok(MonoObject::new(self.ptr as *mut _))
+    }
+}
+
+/// A null object turns into a null array
+impl<'d> FromMono<'d> for MonoArray<'d> {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        if !obj.is_null() {
+            expect_instance(obj, &MonoArray::mono_class(&current_domain()?)?)?;
+        }
+        Ok(MonoArray::new(obj.ptr as *mut _))
+    }
+}
+
+impl ToMono for MonoDelegate<'_> {
+    fn mono_class(_domain: &Domain) -> Result<MonoClass<'_>> {
+        corlib_class("System", "Delegate")
+    }
+
+    fn to_mono<'d>(&self, _domain: &'d Domain) -> Result<MonoObject<'d>> {
+        Ok(MonoObject::new(self.ptr))
+    }
+}
+
+/// A null object turns into a null delegate
+impl<'d> FromMono<'d> for MonoDelegate<'d> {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        if !obj.is_null() {
+            expect_instance(obj, &MonoDelegate::mono_class(&current_domain()?)?)?;
+        }
+        Ok(MonoDelegate::new(obj.ptr))
+    }
+}
+
+/// `None` is null, which for value types means a `System.Nullable<T>` without a value
+impl<T: ToMono> ToMono for Option<T> {
+    fn mono_class(domain: &Domain) -> Result<MonoClass<'_>> {
+        let class = T::mono_class(domain)?;
+        if class.is_valuetype() {
+            generic_instance("System.Nullable`1", &[class])
+        } else {
+            Ok(class)
+        }
+    }
+
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+        match self {
+            Some(value) => value.to_mono(domain),
+            None => Ok(MonoObject::new(null_mut())),
+        }
+    }
+}
+
+impl<'d, T: FromMono<'d>> FromMono<'d> for Option<T> {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        if obj.is_null() {
+            Ok(None)
+        } else {
+            T::from_mono(obj).map(Some)
+        }
+    }
+}
+
+/// Converts into a one-dimensional array
+impl<T: ToMono> ToMono for &[T] {
+    fn mono_class(domain: &Domain) -> Result<MonoClass<'_>> {
+        class_from_ptr(unsafe { sys::mono_array_class_get(T::mono_class(domain)?.raw.as_ptr(), 1) })
+    }
+
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+        let eclass = T::mono_class(domain)?;
+        let array = domain.create_array(&eclass, self.len());
+
+        for (i, value) in self.iter().enumerate() {
+            let obj = value.to_mono(domain)?;
+            if eclass.is_valuetype() {
+                let (_obj, ptr) = to_native(obj, &eclass, domain)?;
+                unsafe {
+                    let size = sys::mono_class_array_element_size(eclass.raw.as_ptr());
+                    let addr = array.get(size, i);
+                    sys::mono_gc_wbarrier_value_copy(
+                        addr as *mut c_void,
+                        ptr,
+                        1,
+                        eclass.raw.as_ptr(),
+                    );
+                }
+            } else {
+                array.set_ref(i, &obj);
+            }
+        }
+
+        Ok(MonoObject::new(array.ptr as *mut _))
+    }
+}
+
+impl<T: ToMono> ToMono for Vec<T> {
+    fn mono_class(domain: &Domain) -> Result<MonoClass<'_>> {
+        <&[T]>::mono_class(domain)
+    }
+
+    fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+        self.as_slice().to_mono(domain)
+    }
+}
+
+/// Reads a one-dimensional array, a null array is an error
+impl<'d, T: FromMono<'d>> FromMono<'d> for Vec<T> {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        let array = MonoArray::from_mono(obj)?;
+        if array.is_null() {
+            return Err(anyhow!("Expected an array but got null"));
+        }
+
+        let class = obj.get_class();
+        unsafe {
+            if sys::mono_class_get_rank(class.raw.as_ptr()) != 1 {
+                return Err(anyhow!("Expected a one-dimensional array"));
+            }
+            let eclass = sys::mono_class_get_element_class(class.raw.as_ptr());
+            let size = sys::mono_class_array_element_size(eclass);
+            let domain = sys::mono_object_get_domain(obj.ptr);
+
+            (0..array.length())
+                .map(|i| {
+                    let addr = array.get(size, i) as *mut c_void;
+                    let element = if sys::mono_class_is_valuetype(eclass) != 0 {
+                        sys::mono_value_box(domain, eclass, addr)
+                    } else {
+                        *(addr as *mut *mut sys::MonoObject)
+                    };
+                    T::from_mono(&MonoObject::new(element))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Void methods return null, which is the only thing that turns into `()`
+impl<'d> FromMono<'d> for () {
+    fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+        if !obj.is_null() {
+            return Err(anyhow!(
+                "Expected nothing but got a {}",
+                obj.get_class().get_name()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Tuples convert to and from `System.ValueTuple`
+macro_rules! impl_tuple {
+    ($count:tt; $($arg:ident $idx:tt $item:tt),*) => {
+        impl<$($arg: ToMono),*> ToMono for ($($arg,)*) {
+            fn mono_class(domain: &Domain) -> Result<MonoClass<'_>> {
+                generic_instance(
+                    concat!("System.ValueTuple`", $count),
+                    &[$($arg::mono_class(domain)?),*],
+                )
+            }
+
+            fn to_mono<'d>(&self, domain: &'d Domain) -> Result<MonoObject<'d>> {
+                let class = Self::mono_class(domain)?;
+                let obj = domain.create_object(&class);
+                $(
+                    let field = class
+                        .get_field_from_name($item)
+                        .ok_or_else(|| anyhow!("ValueTuple is missing {}", $item))?;
+                    set_field_value(&obj, &field, self.$idx.to_mono(domain)?, domain)?;
+                )*
+                Ok(obj)
+            }
+        }
+
+        impl<'d, $($arg: FromMono<'d>),*> FromMono<'d> for ($($arg,)*) {
+            fn from_mono(obj: &MonoObject<'d>) -> Result<Self> {
+                if obj.is_null() {
+                    return Err(anyhow!("Expected a ValueTuple but got null"));
+                }
+                let class = obj.get_class();
+                if class.get_name() != concat!("ValueTuple`", $count) {
+                    return Err(anyhow!("Expected a ValueTuple but got {}", class.get_name()));
+                }
+                Ok(($(
+                    {
+                        let field = class
+                            .get_field_from_name($item)
+                            .ok_or_else(|| anyhow!("ValueTuple is missing {}", $item))?;
+                        $arg::from_mono(&get_field_value(obj, &field))?
+                    },
+                )*))
+            }
+        }
+    };
+}
+
+impl_tuple!(1; A 0 "Item1");
+impl_tuple!(2; A 0 "Item1", B 1 "Item2");
+impl_tuple!(3; A 0 "Item1", B 1 "Item2", C 2 "Item3");
+impl_tuple!(4; A 0 "Item1", B 1 "Item2", C 2 "Item3", D 3 "Item4");
+impl_tuple!(5; A 0 "Item1", B 1 "Item2", C 2 "Item3", D 3 "Item4", E 4 "Item5");
+impl_tuple!(6; A 0 "Item1", B 1 "Item2", C 2 "Item3", D 3 "Item4", E 4 "Item5", F 5 "Item6");
+impl_tuple!(7; A 0 "Item1", B 1 "Item2", C 2 "Item3", D 3 "Item4", E 4 "Item5", F 5 "Item6", G 6 "Item7");
+
+/// A tuple of arguments for [`Method::call`](crate::Method::call)
+pub trait MethodArgs {
+    const COUNT: usize;
+
+    fn to_mono_args<'d>(&self, domain: &'d Domain) -> Result<Vec<MonoObject<'d>>>;
+}
+
+macro_rules! impl_method_args {
+    ($count:expr; $($arg:ident $idx:tt),*) => {
+        impl<$($arg: ToMono),*> MethodArgs for ($($arg,)*) {
+            const COUNT: usize = $count;
+
+            #[allow(unused_variables)]
+            fn to_mono_args<'d>(&self, domain: &'d Domain) -> Result<Vec<MonoObject<'d>>> {
+                Ok(vec![$(self.$idx.to_mono(domain)?),*])
+            }
+        }
+    };
+}
+
+impl_method_args!(0;);
+impl_method_args!(1; A 0);
+impl_method_args!(2; A 0, B 1);
+impl_method_args!(3; A 0, B 1, C 2);
+impl_method_args!(4; A 0, B 1, C 2, D 3);
+impl_method_args!(5; A 0, B 1, C 2, D 3, E 4);
+impl_method_args!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_method_args!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_method_args!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_method_args!(9; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_method_args!(10; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_method_args!(11; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_method_args!(12; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+impl_method_args!(13; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12);
+impl_method_args!(14; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13);
+impl_method_args!(15; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14);
+impl_method_args!(16; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15);
@@ -0,0 +1,159 @@
+//! Runtime support for the code generated by `#[derive(MonoType)]`
+
+use crate::convert::{self, ToMono};
+use crate::{Domain, FromMono, MonoClass, MonoClassField, MonoObject};
+use anyhow::{anyhow, Result};
+use std::ptr::null_mut;
+
+/// A field of the rust struct, as seen by the derive
+pub struct Field {
+    /// Name of the managed field
+    pub name: &'static str,
+    pub class: fn(&Domain) -> Result<MonoClass<'_>>,
+    /// Offset inside the rust struct
+    pub offset: usize,
+}
+
+fn instance_fields<'d>(class: &MonoClass<'d>) -> Vec<MonoClassField<'d>> {
+    class
+        .get_fields()
+        .filter(|field| !field.is_static())
+        .collect()
+}
+
+/// Looks up `namespace.name` and checks that its instance fields match `fields`
+/// in name, order and type.
+///
+/// `size` is only given for `#[repr(C)]` structs, in which case value types
+/// also have their size and field offsets checked
+///
+/// The result is cached per rust type and domain, the checks only run the first time
+pub fn find_class<'d, T: 'static>(
+    domain: &'d Domain,
+    namespace: &str,
+    name: &str,
+    fields: &[Field],
+    size: Option<usize>,
+) -> Result<MonoClass<'d>> {
+    convert::cached_class::<T>(domain, || {
+        validate_class(domain, namespace, name, fields, size)
+    })
+}
+
+fn validate_class<'d>(
+    domain: &'d Domain,
+    namespace: &str,
+    name: &str,
+    fields: &[Field],
+    size: Option<usize>,
+) -> Result<MonoClass<'d>> {
+    let class = crate::assembly::find_loaded_class(domain, namespace, name)
+        .ok_or_else(|| anyhow!("Could not find class {}.{}", namespace, name))?;
+
+    let managed = instance_fields(&class);
+    let managed_names = managed
+        .iter()
+        .map(MonoClassField::get_name)
+        .collect::<Vec<_>>();
+    let rust_names = fields.iter().map(|f| f.name).collect::<Vec<_>>();
+    if managed_names != rust_names {
+        return Err(anyhow!(
+            "{}.{} has the fields {:?} but the rust struct has {:?}",
+            namespace,
+            name,
+            managed_names,
+            rust_names
+        ));
+    }
+
+    for (field, managed) in fields.iter().zip(managed.iter()) {
+        let expected = (field.class)(domain)?;
+        let ty = managed.get_type();
+        let actual = unsafe { sys::mono_class_from_mono_type(ty.raw.as_ptr()) };
+        if actual != expected.raw.as_ptr() {
+            return Err(anyhow!(
+                "{}.{}::{} is {} but the rust field is {}",
+                namespace,
+                name,
+                field.name,
+                ty.name(),
+                expected.get_type().name()
+            ));
+        }
+    }
+
+    let is_valuetype = class.is_valuetype();
+    if let (Some(size), true) = (size, is_valuetype) {
+        let managed_size = unsafe { sys::mono_class_value_size(class.raw.as_ptr(), null_mut()) };
+        if managed_size as usize != size {
+            return Err(anyhow!(
+                "{}.{} is {} bytes but the rust struct is {} bytes",
+                namespace,
+                name,
+                managed_size,
+                size
+            ));
+        }
+
+        // offsets of value type fields include the header of the boxed object
+        let header = std::mem::size_of::<sys::MonoObject>();
+        for (field, managed) in fields.iter().zip(managed.iter()) {
+            let offset = unsafe { sys::mono_field_get_offset(managed.raw.as_ptr()) } as usize;
+            let offset = offset.checked_sub(header).ok_or_else(|| {
+                anyhow!(
+                    "{}.{}::{} is at offset {}, inside the object header",
+                    namespace,
+                    name,
+                    field.name,
+                    offset
+                )
+            })?;
+            if offset != field.offset {
+                return Err(anyhow!(
+                    "{}.{}::{} is at offset {} but the rust field is at {}",
+                    namespace,
+                    name,
+                    field.name,
+                    offset,
+                    field.offset
+                ));
+            }
+        }
+    }
+
+    Ok(class)
+}
+
+/// Runs `f` with the domain `from_mono` looks classes up in
+pub fn with_current_domain<R>(f: impl FnOnce(&Domain) -> Result<R>) -> Result<R> {
+    let domain = Domain::current().ok_or_else(|| anyhow!("No domain loaded"))?;
+    f(&domain)
+}
+
+pub fn expect_instance(obj: &MonoObject<'_>, class: &MonoClass<'_>) -> Result<()> {
+    convert::expect_instance(obj, class)
+}
+
+pub fn set_field<T: ToMono>(
+    obj: &MonoObject<'_>,
+    class: &MonoClass<'_>,
+    name: &str,
+    value: &T,
+    domain: &Domain,
+) -> Result<()> {
+    let field = class
+        .get_field_from_name(name)
+        .ok_or_else(|| anyhow!("{} has no field {}", class.get_name(), name))?;
+    convert::set_field_value(obj, &field, value.to_mono(domain)?, domain)
+}
+
+pub fn get_field<'d, T: FromMono<'d>>(
+    obj: &MonoObject<'d>,
+    class: &MonoClass<'_>,
+    name: &str,
+) -> Result<T> {
+    let field = class
+        .get_field_from_name(name)
+        .ok_or_else(|| anyhow!("{} has no field {}", class.get_name(), name))?;
+    T::from_mono(&convert::get_field_value(obj, &field))
+}
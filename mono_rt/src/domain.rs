@@ -1,115 +1,352 @@
+use crate::class::corlib_method;
+use crate::closure;
+use crate::convert;
+use crate::exception::{self, MonoException};
+use crate::icall::{self, IcallArg, IcallReturn};
 use crate::{
-    osstr_to_cstring, Assembly, MonoArray, MonoClass, MonoClassField, MonoObject, MonoString,
+    osstr_to_cstring, Assembly, AssemblyName, Method, MonoArray, MonoClass, MonoClassField,
+    MonoObject, MonoString, MonoType,
 };
 use anyhow::{anyhow, Result};
-use std::ffi::{c_void, CString};
+use std::convert::TryFrom;
+use std::ffi::{c_void, CStr, CString};
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
 use std::path::Path;
-use std::ptr::NonNull;
+use std::ptr::{null_mut, NonNull};
+use std::sync::atomic::{AtomicU8, Ordering};
 
-static mut DOMAIN_CREATED: bool = false;
-static mut DOMAIN_DROPPED: bool = false;
+const JIT_UNINITIALIZED: u8 = 0;
+const JIT_RUNNING: u8 = 1;
+const JIT_CLEANED_UP: u8 = 2;
 
+/// The JIT can be initialized once per process, and not again after it has been cleaned up
+static JIT_STATE: AtomicU8 = AtomicU8::new(JIT_UNINITIALIZED);
+
+/// What happens to a domain when its handle is dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Ownership {
+    /// The root domain, dropping it cleans up the JIT along with every other domain
+    Root,
+    /// A child domain, dropping it unloads the domain
+    Child,
+    /// A handle to a domain owned elsewhere
+    Borrowed,
+}
+
+/// A handle to an application domain
+///
+/// Classes, methods, objects and the other runtime handles borrow the domain they were
+/// obtained from, so they can not be used once it is unloaded or the JIT is cleaned up.
+/// Handles that do not own their domain, like the one [`get`](Self::get) returns,
+/// carry no such guarantee, which is why getting one is unsafe.
 #[non_exhaustive]
 pub struct Domain {
     pub(crate) raw: NonNull<mono_sys::MonoDomain>,
+    ownership: Ownership,
+}
+
+impl PartialEq for Domain {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
 }
 
 impl Drop for Domain {
     fn drop(&mut self) {
-        unsafe {
-            if !DOMAIN_DROPPED {
-                DOMAIN_DROPPED = true;
-                mono_sys::mono_jit_cleanup(self.raw.as_ptr());
-            } else {
-                panic!("Cannot drop a domain twice")
+        match self.ownership {
+            Ownership::Root => {
+                JIT_STATE.store(JIT_CLEANED_UP, Ordering::SeqCst);
+                unsafe { mono_sys::mono_jit_cleanup(self.raw.as_ptr()) };
+                closure::drop_all();
+                convert::drop_all();
+            }
+            Ownership::Child => {
+                // Child domains are torn down along with the root domain
+                if jit_running() {
+                    let _ = self.unload_raw();
+                }
             }
+            Ownership::Borrowed => {}
         }
     }
 }
 
+pub(crate) fn jit_initialized() -> bool {
+    JIT_STATE.load(Ordering::SeqCst) != JIT_UNINITIALIZED
+}
+
+fn jit_running() -> bool {
+    JIT_STATE.load(Ordering::SeqCst) == JIT_RUNNING
+}
+
+fn start_jit() -> Result<()> {
+    JIT_STATE
+        .compare_exchange(
+            JIT_UNINITIALIZED,
+            JIT_RUNNING,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        )
+        .map(|_| ())
+        .map_err(|_| anyhow!("The JIT can only be initialized once per process"))
+}
+
 impl Domain {
+    /// Initializes the JIT, returning the root domain,
+    /// see [`RuntimeBuilder`](crate::RuntimeBuilder) for more options
     pub fn new(name: &str) -> Result<Self> {
-        unsafe {
-            if DOMAIN_CREATED || DOMAIN_DROPPED {
-                panic!("Cannot create a domain twice");
+        Self::init(name, None)
+    }
+
+    /// Initializes the JIT for a given runtime version, returning the root domain
+    pub fn new_with_version(name: &str, version: &str) -> Result<Self> {
+        Self::init(name, Some(version))
+    }
+
+    pub(crate) fn init(name: &str, version: Option<&str>) -> Result<Self> {
+        let name_cstr = CString::new(name)?;
+        let version_cstr = version.map(CString::new).transpose()?;
+        start_jit()?;
+
+        let ptr = unsafe {
+            match &version_cstr {
+                Some(version) => {
+                    mono_sys::mono_jit_init_version(name_cstr.as_ptr(), version.as_ptr())
+                }
+                None => mono_sys::mono_jit_init(name_cstr.as_ptr()),
             }
-            DOMAIN_CREATED = true;
+        };
+        let raw = NonNull::new(ptr).ok_or(anyhow!("Failed to initialize the JIT"))?;
+        Ok(Self {
+            raw,
+            ownership: Ownership::Root,
+        })
+    }
+
+    /// A handle to a domain owned elsewhere, dropping it does nothing
+    pub(crate) fn borrowed(ptr: *mut sys::MonoDomain) -> Option<Self> {
+        if !jit_running() {
+            return None;
         }
+        Some(Self {
+            raw: NonNull::new(ptr)?,
+            ownership: Ownership::Borrowed,
+        })
+    }
 
-        let name_cstr = CString::new(name)?;
+    /// The domain of the current thread, the handle does not own it
+    ///
+    /// # Safety
+    /// The handle and everything obtained through it must not be used after the domain
+    /// is unloaded or the root domain is dropped, see [`foreach`](Self::foreach)
+    /// for a handle that can not escape
+    pub unsafe fn get() -> Option<Domain> {
+        Self::current()
+    }
 
-        let ptr = unsafe { mono_sys::mono_jit_init(name_cstr.as_ptr()) };
-        let raw = NonNull::new(ptr).expect("mono_jit_init returned null");
-        Ok(Self { raw })
+    /// The root domain, the handle does not own it
+    ///
+    /// # Safety
+    /// The handle and everything obtained through it must not be used after
+    /// the root domain is dropped
+    pub unsafe fn root() -> Option<Domain> {
+        Self::borrowed(sys::mono_get_root_domain())
     }
 
-    pub fn new_with_version(name: &str, version: &str) -> Result<Self> {
+    /// Looks up a loaded domain by its id, the handle does not own it
+    ///
+    /// # Safety
+    /// The handle and everything obtained through it must not be used after the domain
+    /// is unloaded or the root domain is dropped
+    pub unsafe fn by_id(id: i32) -> Option<Domain> {
+        if !jit_running() {
+            return None;
+        }
+        Self::borrowed(sys::mono_domain_get_by_id(id))
+    }
+
+    /// The domain of the current thread, for use within a call that already
+    /// holds a handle tied to a live domain
+    pub(crate) fn current() -> Option<Domain> {
+        Self::borrowed(unsafe { sys::mono_domain_get() })
+    }
+
+    /// Calls `f` with a handle to every loaded domain, root domain included
+    pub fn foreach<F: FnMut(&Domain)>(mut f: F) {
+        unsafe extern "C" fn callback<F: FnMut(&Domain)>(
+            domain: *mut sys::MonoDomain,
+            user_data: *mut c_void,
+        ) {
+            let f = &mut *(user_data as *mut F);
+            if let Some(domain) = Domain::borrowed(domain) {
+                f(&domain);
+            }
+        }
+
+        if jit_running() {
+            unsafe { sys::mono_domain_foreach(Some(callback::<F>), &mut f as *mut F as *mut _) };
+        }
+    }
+
+    /// Creates a child `AppDomain`, which is unloaded when the handle is dropped
+    ///
+    /// Assemblies loaded into a child domain go away with it,
+    /// see [`set`](Self::set) to run code inside it
+    pub fn create_appdomain(
+        &self,
+        friendly_name: &str,
+        config_file: Option<&Path>,
+    ) -> Result<Domain> {
+        let name_cstr = CString::new(friendly_name)?;
+        let config_cstr = config_file
+            .map(|path| osstr_to_cstring(path.as_os_str()))
+            .transpose()?;
+
+        let ptr = unsafe {
+            sys::mono_domain_create_appdomain(
+                name_cstr.as_ptr() as *mut _,
+                config_cstr
+                    .as_ref()
+                    .map_or(null_mut(), |c| c.as_ptr() as *mut _),
+            )
+        };
+        let raw = NonNull::new(ptr).ok_or(anyhow!("mono_domain_create_appdomain returned null"))?;
+        Ok(Self {
+            raw,
+            ownership: Ownership::Child,
+        })
+    }
+
+    /// Makes this the domain of the current thread,
+    /// fails if the domain is being unloaded unless `force` is set
+    pub fn set(&self, force: bool) -> Result<()> {
+        if unsafe { sys::mono_domain_set(self.raw.as_ptr(), force as _) } == 0 {
+            return Err(anyhow!("{} is being unloaded", self.friendly_name()));
+        }
+        Ok(())
+    }
+
+    pub fn id(&self) -> i32 {
+        unsafe { sys::mono_domain_get_id(self.raw.as_ptr()) }
+    }
+
+    pub fn friendly_name(&self) -> String {
         unsafe {
-            if DOMAIN_CREATED {
-                panic!("Cannot create a domain twice");
+            let ptr = sys::mono_domain_get_friendly_name(self.raw.as_ptr());
+            if ptr.is_null() {
+                return String::new();
             }
-            DOMAIN_CREATED = true;
+            CStr::from_ptr(ptr).to_string_lossy().into_owned()
         }
+    }
 
-        let name_cstr = CString::new(name)?;
-        let version_cstr = CString::new(version)?;
+    pub fn is_root(&self) -> bool {
+        self.raw.as_ptr() == unsafe { sys::mono_get_root_domain() }
+    }
 
-        let ptr =
-            unsafe { mono_sys::mono_jit_init_version(name_cstr.as_ptr(), version_cstr.as_ptr()) };
-        let raw = NonNull::new(ptr).ok_or(anyhow!("mono_jit_init_version returned null"))?;
-        Ok(Self { raw })
+    pub fn is_unloading(&self) -> bool {
+        unsafe { sys::mono_domain_is_unloading(self.raw.as_ptr()) != 0 }
+    }
+
+    /// Unloads a child domain, see [`try_unload`](Self::try_unload)
+    pub fn unload(self) -> Result<()> {
+        self.try_unload().map_err(|(_, e)| e)
+    }
+
+    /// Unloads a child domain, giving it back along with the error if that fails
+    ///
+    /// Threads running in the domain are aborted first,
+    /// the current thread moves to the root domain if it was inside it
+    /// and moves back if the unload fails.
+    /// Only the handle returned by [`create_appdomain`](Self::create_appdomain) can unload
+    /// a domain, the root domain is cleaned up by dropping it instead.
+    pub fn try_unload(self) -> Result<(), (Domain, anyhow::Error)> {
+        if self.ownership != Ownership::Child {
+            return Err((self, anyhow!("Only child domains can be unloaded")));
+        }
+        let this = ManuallyDrop::new(self);
+        match this.unload_raw() {
+            Ok(()) => Ok(()),
+            Err(e) => Err((
+                Domain {
+                    raw: this.raw,
+                    ownership: this.ownership,
+                },
+                e.into(),
+            )),
+        }
     }
 
-    /// The domain needs to exist for this to work, if the domain has been dropped,
-    /// or not created in the first place this will return None
-    pub fn get<'d>() -> Option<&'d mut Domain> {
-        if unsafe { !DOMAIN_CREATED || DOMAIN_DROPPED } {
-            None
-        } else {
-            let ptr = unsafe { mono_sys::mono_domain_get() };
-            let x = Box::leak(Box::new(Self {
-                raw: NonNull::new(ptr)?,
-            }));
-            Some(x)
+    fn unload_raw(&self) -> Result<(), MonoException> {
+        unsafe {
+            let root = sys::mono_get_root_domain();
+            let was_current = sys::mono_domain_get() == self.raw.as_ptr();
+            if was_current && root != self.raw.as_ptr() {
+                sys::mono_domain_set(root, 0);
+            }
+            let mut exc = null_mut();
+            sys::mono_domain_try_unload(self.raw.as_ptr(), &mut exc);
+            if let Err(e) = exception::check((), exc) {
+                // the domain is still loaded, so the thread goes back into it
+                if was_current {
+                    sys::mono_domain_set(self.raw.as_ptr(), 0);
+                }
+                return Err(e);
+            }
         }
+        closure::drop_domain(self.raw);
+        convert::drop_domain(self.raw);
+        Ok(())
     }
 
+    /// # Safety
+    /// `f` has to be an `extern "C"` function matching the signature of the managed method
     // Although it does not use the domain to will fail without a domain loaded
-    unsafe fn add_internal_call_raw(&self, path: &str, f: *const c_void) -> Result<()> {
+    pub unsafe fn add_internal_call_raw(&self, path: &str, f: *const c_void) -> Result<()> {
         let cstr = CString::new(path)?;
         mono_sys::mono_add_internal_call(cstr.as_ptr(), f);
         Ok(())
     }
 
-    // pub fn add_internal_call(&self, path: &str, f: &dyn FunctionToPtr) -> Result<()> {
-    //     unsafe { self.add_internal_call_raw(path, f.to_raw_ptr()) }
-    // }
-    // pub fn add_internal_call<T: FunctionToPtr>(&self, path: &str, f: T) -> Result<()> {
-    //     unsafe { self.add_internal_call_raw(path, f.to_raw_ptr()) }
-    // }
+    /// Registers a function or a closure that captures nothing, of up to 16 parameters,
+    /// as an internal call
+    ///
+    /// Arguments and the return value are converted through [`IcallArg`] and [`IcallReturn`].
+    /// A panic is caught at the boundary and thrown into the calling managed code instead,
+    /// see [`icall::set_exception_class`](crate::icall::set_exception_class).
+    /// Closures that capture anything fail to compile, see
+    /// [`add_internal_call_closure`](Self::add_internal_call_closure) for those.
+    ///
+    /// `extern "C"` function pointers are registered as they are, without any conversion.
+    /// Function items have to be cast to a function pointer first, e.g.
+    /// `domain.add_internal_call("App.Hello::Add", add as extern "C" fn(_, _) -> _)`
+    pub fn add_internal_call<Args, F: InternalCallFn<Args>>(&self, path: &str, f: F) -> Result<()> {
+        unsafe { self.add_internal_call_raw(path, f.to_raw_ptr()) }
+    }
 
     pub fn add_internal_call0<Ret>(&self, path: &str, f: extern "C" fn() -> Ret) -> Result<()> {
-        unsafe { self.add_internal_call_raw(path, f as *const _) }
+        self.add_internal_call(path, f)
     }
     pub fn add_internal_call1<Ret, A>(&self, path: &str, f: extern "C" fn(A) -> Ret) -> Result<()> {
-        unsafe { self.add_internal_call_raw(path, f as *const _) }
+        self.add_internal_call(path, f)
     }
     pub fn add_internal_call2<Ret, A, B>(
         &self,
         path: &str,
         f: extern "C" fn(A, B) -> Ret,
     ) -> Result<()> {
-        unsafe { self.add_internal_call_raw(path, f as *const _) }
+        self.add_internal_call(path, f)
     }
     pub fn add_internal_call3<Ret, A, B, C>(
         &self,
         path: &str,
         f: extern "C" fn(A, B, C) -> Ret,
     ) -> Result<()> {
-        unsafe { self.add_internal_call_raw(path, f as *const _) }
+        self.add_internal_call(path, f)
     }
 
-    pub fn open_assembly<P: AsRef<Path>>(&self, path: P) -> Result<Assembly> {
+    pub fn open_assembly<P: AsRef<Path>>(&self, path: P) -> Result<Assembly<'_>> {
         let path = path.as_ref();
         let path_cstr = osstr_to_cstring(path.as_os_str())?;
 
@@ -121,20 +358,170 @@ impl Domain {
         Ok(Assembly {
             raw,
             path_cstr: path_cstr.clone(),
+            _m: PhantomData,
         })
     }
 
-    pub fn create_object(&self, class: &MonoClass) -> MonoObject {
+    /// Loads an assembly from memory, `name` stands in for its path
+    ///
+    /// The bytes are copied, so the buffer can be dropped right away
+    pub fn load_assembly_from_bytes(&self, name: &str, data: &[u8]) -> Result<Assembly<'_>> {
+        self.load_assembly_from_memory(name, data, None)
+    }
+
+    /// Loads an assembly from memory along with its portable PDB or MDB symbols
+    ///
+    /// The symbols are only used when debugging is enabled,
+    /// see [`RuntimeBuilder::debug`](crate::RuntimeBuilder::debug)
+    pub fn load_assembly_from_bytes_with_symbols(
+        &self,
+        name: &str,
+        data: &[u8],
+        symbols: &[u8],
+    ) -> Result<Assembly<'_>> {
+        self.load_assembly_from_memory(name, data, Some(symbols))
+    }
+
+    fn load_assembly_from_memory(
+        &self,
+        name: &str,
+        data: &[u8],
+        symbols: Option<&[u8]>,
+    ) -> Result<Assembly<'_>> {
+        let name_cstr = CString::new(name)?;
+        let data_len = u32::try_from(data.len())
+            .map_err(|_| anyhow!("{} is too large to be an assembly", name))?;
+        let symbols_len = symbols
+            .map(|symbols| i32::try_from(symbols.len()))
+            .transpose()
+            .map_err(|_| anyhow!("The symbols of {} are too large", name))?;
+
+        let ptr = self.with_current(|| unsafe {
+            let mut status = sys::MonoImageOpenStatus_MONO_IMAGE_OK;
+            let image = sys::mono_image_open_from_data_with_name(
+                data.as_ptr() as *mut _,
+                data_len,
+                1,
+                &mut status,
+                0,
+                name_cstr.as_ptr(),
+            );
+            if image.is_null() {
+                Err(image_error("mono_image_open_from_data_with_name", status))
+            } else {
+                if let (Some(symbols), Some(symbols_len)) = (symbols, symbols_len) {
+                    sys::mono_debug_open_image_from_memory(image, symbols.as_ptr(), symbols_len);
+                }
+                let ptr =
+                    sys::mono_assembly_load_from_full(image, name_cstr.as_ptr(), &mut status, 0);
+                // The assembly holds its own reference to the image
+                sys::mono_image_close(image);
+                if ptr.is_null() {
+                    Err(image_error("mono_assembly_load_from_full", status))
+                } else {
+                    Ok(ptr)
+                }
+            }
+        })??;
+
+        Ok(Assembly {
+            raw: NonNull::new(ptr).unwrap(),
+            path_cstr: name_cstr,
+            _m: PhantomData,
+        })
+    }
+
+    /// The assemblies loaded into this domain
+    pub fn assemblies(&self) -> Result<Vec<Assembly<'_>>> {
+        // mono keeps one list for the whole process,
+        // filtered by what AppDomain.GetAssemblies reports for this domain
+        let names = self.with_current(|| self.assembly_full_names())??;
+        Ok(loaded_assemblies()
+            .into_iter()
+            .map(Assembly::from_raw)
+            .filter(|assembly| names.contains(&assembly.get_name().full_name()))
+            .collect())
+    }
+
+    fn assembly_full_names(&self) -> Result<Vec<String>> {
+        let current = corlib_method("System.AppDomain:get_CurrentDomain()")?.call(None, ())?;
+        let assemblies: Vec<MonoObject> =
+            corlib_method("System.AppDomain:GetAssemblies()")?.call_as(Some(&current), ())?;
+        let full_name = corlib_method("System.Reflection.Assembly:get_FullName()")?;
+        assemblies
+            .iter()
+            .map(|assembly| {
+                // overridden by the runtime's assembly class
+                let ptr = unsafe {
+                    sys::mono_object_get_virtual_method(assembly.ptr, full_name.raw.as_ptr())
+                };
+                NonNull::new(ptr)
+                    .map(Method::new)
+                    .ok_or_else(|| anyhow!("Assembly.FullName not found"))?
+                    .call_as(Some(assembly), ())
+            })
+            .collect()
+    }
+
+    /// Looks up an assembly by its simple or full name, like `System`
+    /// or `System, Version=4.0.0.0`
+    ///
+    /// An assembly that is not loaded yet is loaded from the assembly search paths
+    pub fn assembly_by_name(&self, name: &str) -> Result<Assembly<'_>> {
+        let aname = AssemblyName::parse(name)?;
+        let ptr = self.with_current(|| unsafe {
+            let mut ptr = sys::mono_assembly_loaded(aname.raw.as_ptr());
+            let mut status = sys::MonoImageOpenStatus_MONO_IMAGE_OK;
+            if ptr.is_null() {
+                ptr = sys::mono_assembly_load(aname.raw.as_ptr(), null_mut(), &mut status);
+            }
+            NonNull::new(ptr)
+                .ok_or_else(|| anyhow!("Failed to load {}: {}", name, image_status_message(status)))
+        })??;
+        Ok(Assembly::from_raw(ptr))
+    }
+
+    /// Looks up a type by its reflection name, like `App.Person, Program`,
+    /// `System.Collections.Generic.List`1[System.Int32]` or `System.String[]`
+    ///
+    /// Names without an assembly are looked up in `mscorlib`,
+    /// a named assembly is loaded if it is not loaded yet
+    pub fn find_type(&self, name: &str) -> Option<MonoType<'_>> {
+        // mono parses the name in place
+        let mut name = CString::new(name).ok()?.into_bytes_with_nul();
+        let ptr = self
+            .with_current(|| unsafe {
+                sys::mono_reflection_type_from_name(name.as_mut_ptr() as *mut _, null_mut())
+            })
+            .ok()?;
+        NonNull::new(ptr).map(MonoType::new)
+    }
+
+    /// Runs `f` with this as the current domain, which is where mono loads assemblies
+    pub(crate) fn with_current<T>(&self, f: impl FnOnce() -> T) -> Result<T> {
+        let previous = unsafe { sys::mono_domain_get() };
+        if previous == self.raw.as_ptr() {
+            return Ok(f());
+        }
+        self.set(false)?;
+        let result = f();
+        if !previous.is_null() {
+            unsafe { sys::mono_domain_set(previous, 0) };
+        }
+        Ok(result)
+    }
+
+    pub fn create_object(&self, class: &MonoClass<'_>) -> MonoObject<'_> {
         let ptr = unsafe { sys::mono_object_new(self.raw.as_ptr(), class.raw.as_ptr()) };
         MonoObject::new(ptr)
     }
 
-    pub fn create_array(&self, eclass: &MonoClass, n: usize) -> MonoArray {
+    pub fn create_array(&self, eclass: &MonoClass<'_>, n: usize) -> MonoArray<'_> {
         let ptr = unsafe { sys::mono_array_new(self.raw.as_ptr(), eclass.raw.as_ptr(), n) };
         MonoArray::new(ptr)
     }
 
-    pub fn create_string(&self, str: &str) -> Result<MonoString> {
+    pub fn create_string(&self, str: &str) -> Result<MonoString<'_>> {
         let cstr = CString::new(str)?;
         let ptr = unsafe { mono_sys::mono_string_new(self.raw.as_ptr(), cstr.as_ptr()) };
         Ok(MonoString::new(ptr))
@@ -142,38 +529,145 @@ impl Domain {
 
     pub unsafe fn value_box(
         &self,
-        class: &MonoClass,
+        class: &MonoClass<'_>,
         value: *mut std::os::raw::c_void,
-    ) -> MonoObject {
+    ) -> MonoObject<'_> {
         let ptr = sys::mono_value_box(self.raw.as_ptr(), class.raw.as_ptr(), value);
         MonoObject::new(ptr)
     }
 
     pub unsafe fn field_get_value_object(
         &self,
-        field: &MonoClassField,
-        obj: &MonoObject,
-    ) -> MonoObject {
+        field: &MonoClassField<'_>,
+        obj: &MonoObject<'_>,
+    ) -> MonoObject<'_> {
         let ptr = sys::mono_field_get_value_object(self.raw.as_ptr(), field.raw.as_ptr(), obj.ptr);
         MonoObject::new(ptr)
     }
 }
 
-// pub trait FunctionToPtr {
-//     fn to_raw_ptr(self) -> *const std::os::raw::c_void;
-// }
-
-// macro_rules! function_to_ptr {
-//     ($($arg:tt)*) => {
-//         impl<Ret, $($arg)*> FunctionToPtr for extern "C" fn($($arg)*) -> Ret {
-//             fn to_raw_ptr(self) -> *const std::os::raw::c_void {
-//                 self as *const _
-//             }
-//         }
-//     };
-// }
-
-// function_to_ptr!();
-// function_to_ptr!(A);
-// function_to_ptr!(A, B);
-// function_to_ptr!(A, B, C);
+fn image_error(function: &str, status: sys::MonoImageOpenStatus) -> anyhow::Error {
+    anyhow!("{} failed: {}", function, image_status_message(status))
+}
+
+/// Every assembly loaded in the process, whichever domain it was loaded into
+fn loaded_assemblies() -> Vec<NonNull<sys::MonoAssembly>> {
+    unsafe extern "C" fn collect(assembly: *mut c_void, user_data: *mut c_void) {
+        let assemblies = &mut *(user_data as *mut Vec<NonNull<sys::MonoAssembly>>);
+        assemblies.extend(NonNull::new(assembly as *mut sys::MonoAssembly));
+    }
+
+    let mut assemblies = Vec::new();
+    unsafe {
+        sys::mono_assembly_foreach(
+            Some(collect),
+            &mut assemblies as *mut Vec<NonNull<sys::MonoAssembly>> as *mut c_void,
+        )
+    };
+    assemblies
+}
+
+pub(crate) fn image_status_message(status: sys::MonoImageOpenStatus) -> String {
+    let message = unsafe { CStr::from_ptr(sys::mono_image_strerror(status)) };
+    message.to_string_lossy().into_owned()
+}
+
+mod sealed {
+    pub trait Sealed<Args> {}
+}
+
+/// Implemented for functions, closures without captures and `extern "C"` function pointers
+/// of up to 16 parameters
+pub trait InternalCallFn<Args>: sealed::Sealed<Args> {
+    fn to_raw_ptr(self) -> *const c_void;
+}
+
+/// Fails to compile for closures that capture anything, the shim conjures them out of nothing
+struct AssertZeroSized<F>(F);
+
+impl<F> AssertZeroSized<F> {
+    const OK: () = assert!(
+        std::mem::size_of::<F>() == 0,
+        "internal calls can not capture anything, use add_internal_call_closure"
+    );
+}
+
+macro_rules! internal_call_fn {
+    ($($arg:ident $value:ident),*) => {
+        impl<Func, Ret, $($arg),*> sealed::Sealed<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret,
+        {
+        }
+
+        impl<Func, Ret, $($arg),*> InternalCallFn<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + Copy + 'static,
+            Ret: IcallReturn,
+            $($arg: IcallArg,)*
+        {
+            fn to_raw_ptr(self) -> *const c_void {
+                extern "C" fn shim<Func, Ret, $($arg),*>($($value: $arg::Abi),*) -> Ret::Abi
+                where
+                    Func: Fn($($arg),*) -> Ret + Copy + 'static,
+                    Ret: IcallReturn,
+                    $($arg: IcallArg,)*
+                {
+                    let () = AssertZeroSized::<Func>::OK;
+                    icall::run(|| {
+                        let f: Func = unsafe { std::mem::zeroed() };
+                        Ok(f($($arg::from_abi($value)?),*))
+                    })
+                }
+
+                shim::<Func, Ret, $($arg),*> as *const c_void
+            }
+        }
+
+        // function pointers are registered as they are, `Args` is the pointer type
+        // so these never overlap with the closures above
+        impl<Ret, $($arg),*> sealed::Sealed<extern "C" fn($($arg),*) -> Ret>
+            for extern "C" fn($($arg),*) -> Ret
+        {
+        }
+
+        impl<Ret, $($arg),*> InternalCallFn<extern "C" fn($($arg),*) -> Ret>
+            for extern "C" fn($($arg),*) -> Ret
+        {
+            fn to_raw_ptr(self) -> *const c_void {
+                self as *const c_void
+            }
+        }
+
+        impl<Ret, $($arg),*> sealed::Sealed<unsafe extern "C" fn($($arg),*) -> Ret>
+            for unsafe extern "C" fn($($arg),*) -> Ret
+        {
+        }
+
+        impl<Ret, $($arg),*> InternalCallFn<unsafe extern "C" fn($($arg),*) -> Ret>
+            for unsafe extern "C" fn($($arg),*) -> Ret
+        {
+            fn to_raw_ptr(self) -> *const c_void {
+                self as *const c_void
+            }
+        }
+    };
+}
+
+internal_call_fn!();
+internal_call_fn!(A a);
+internal_call_fn!(A a, B b);
+internal_call_fn!(A a, B b, C c);
+internal_call_fn!(A a, B b, C c, D d);
+internal_call_fn!(A a, B b, C c, D d, E e);
+internal_call_fn!(A a, B b, C c, D d, E e, F f);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o);
+internal_call_fn!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o, P p);
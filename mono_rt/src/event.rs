@@ -0,0 +1,177 @@
+//! Subscribing to managed events from rust
+
+use crate::{ClosureFn, Domain, Gc, Method, MethodArgs, MonoClass, MonoDelegate, MonoObject};
+use anyhow::{anyhow, Result};
+use std::ffi::CStr;
+use std::marker::PhantomData;
+use std::ptr::{null_mut, NonNull};
+
+#[non_exhaustive]
+#[repr(transparent)]
+pub struct MonoEvent<'d> {
+    pub(crate) raw: NonNull<sys::MonoEvent>,
+    _m: PhantomData<&'d Domain>,
+}
+
+impl<'d> MonoEvent<'d> {
+    pub(crate) fn new(raw: NonNull<sys::MonoEvent>) -> Self {
+        Self {
+            raw,
+            _m: PhantomData,
+        }
+    }
+
+    pub fn get_name(&self) -> String {
+        let cstr = unsafe { CStr::from_ptr(sys::mono_event_get_name(self.raw.as_ptr())) };
+        cstr.to_string_lossy().into_owned()
+    }
+
+    /// The class declaring the event
+    pub fn get_parent(&self) -> MonoClass<'d> {
+        let ptr = unsafe { sys::mono_event_get_parent(self.raw.as_ptr()) };
+        MonoClass::new(NonNull::new(ptr).unwrap())
+    }
+
+    /// The `System.Reflection.EventAttributes` of the event
+    pub fn get_flags(&self) -> u32 {
+        unsafe { sys::mono_event_get_flags(self.raw.as_ptr()) }
+    }
+
+    pub fn add_method(&self) -> Option<Method<'d>> {
+        let ptr = unsafe { sys::mono_event_get_add_method(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(Method::new)
+    }
+
+    pub fn remove_method(&self) -> Option<Method<'d>> {
+        let ptr = unsafe { sys::mono_event_get_remove_method(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(Method::new)
+    }
+
+    /// C# does not emit raise methods, see [`raise`](Self::raise)
+    pub fn raise_method(&self) -> Option<Method<'d>> {
+        let ptr = unsafe { sys::mono_event_get_raise_method(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(Method::new)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.add_method().is_some_and(|method| {
+            let flags = unsafe { sys::mono_method_get_flags(method.raw.as_ptr(), null_mut()) };
+            flags & sys::MONO_METHOD_ATTR_STATIC != 0
+        })
+    }
+
+    /// The delegate type of the event's handlers
+    pub fn handler_class(&self) -> Result<MonoClass<'d>> {
+        let add = self.expect_add_method()?;
+        let params = add.signature().params();
+        let ty = match params.as_slice() {
+            [ty] => ty,
+            _ => return Err(anyhow!("{} has an unexpected add method", self.get_name())),
+        };
+        ty.get_class()
+            .ok_or_else(|| anyhow!("{} has no class", ty.name()))
+    }
+
+    /// Adds a handler, `target` is the instance raising the event and `None` for static events
+    pub fn add(&self, target: Option<&MonoObject<'_>>, handler: &MonoDelegate<'_>) -> Result<()> {
+        self.expect_add_method()?
+            .call(target, (MonoDelegate::new(handler.ptr),))?;
+        Ok(())
+    }
+
+    /// Removes a handler added before, removing one that is not subscribed does nothing
+    pub fn remove(
+        &self,
+        target: Option<&MonoObject<'_>>,
+        handler: &MonoDelegate<'_>,
+    ) -> Result<()> {
+        self.remove_method()
+            .ok_or_else(|| anyhow!("{} has no remove method", self.get_name()))?
+            .call(target, (MonoDelegate::new(handler.ptr),))?;
+        Ok(())
+    }
+
+    /// Subscribes a closure, which is unsubscribed when the returned handle is dropped
+    ///
+    /// The closure is wrapped in a delegate as by [`Domain::create_delegate`].
+    pub fn subscribe<Args, C: ClosureFn<Args>>(
+        &self,
+        domain: &'d Domain,
+        target: Option<&MonoObject<'d>>,
+        closure: C,
+    ) -> Result<Subscription<'d>> {
+        let handler = domain.create_delegate(&self.handler_class()?, closure)?;
+        self.add(target, &handler)?;
+        Ok(Subscription {
+            event: MonoEvent::new(self.raw),
+            target: target.map(Gc::new),
+            handler: Some(Gc::new(&handler)),
+        })
+    }
+
+    /// Calls the handlers of the event with a tuple of arguments
+    ///
+    /// Uses the raise method if there is one, otherwise the backing field
+    /// the C# compiler generates for field-like events
+    pub fn raise<A: MethodArgs>(&self, target: Option<&MonoObject<'d>>, args: A) -> Result<()> {
+        if let Some(raise) = self.raise_method() {
+            raise.call(target, args)?;
+            return Ok(());
+        }
+
+        let field = self
+            .get_parent()
+            .get_field_from_name(&self.get_name())
+            .ok_or_else(|| anyhow!("{} has no raise method", self.get_name()))?;
+        let handler: MonoDelegate = match target {
+            Some(target) => field.get(target)?,
+            None => {
+                let domain = Domain::current().ok_or_else(|| anyhow!("No domain loaded"))?;
+                let handler: MonoDelegate = field.get_static(&domain)?;
+                MonoDelegate::new(handler.ptr)
+            }
+        };
+        if !handler.is_null() {
+            handler.call(args)?;
+        }
+        Ok(())
+    }
+
+    fn expect_add_method(&self) -> Result<Method<'d>> {
+        self.add_method()
+            .ok_or_else(|| anyhow!("{} has no add method", self.get_name()))
+    }
+}
+
+/// A handler added by [`MonoEvent::subscribe`], removed again when dropped
+pub struct Subscription<'d> {
+    event: MonoEvent<'d>,
+    target: Option<Gc<MonoObject<'d>>>,
+    handler: Option<Gc<MonoDelegate<'d>>>,
+}
+
+impl<'d> Subscription<'d> {
+    pub fn event(&self) -> &MonoEvent<'d> {
+        &self.event
+    }
+
+    /// Removes the handler, returning the error dropping the handle would ignore
+    pub fn unsubscribe(mut self) -> Result<()> {
+        self.remove()
+    }
+
+    fn remove(&mut self) -> Result<()> {
+        let handler = match self.handler.take() {
+            Some(handler) => handler,
+            None => return Ok(()),
+        };
+        let target = self.target.as_ref().map(|target| target.get());
+        self.event.remove(target.as_deref(), &handler.get())
+    }
+}
+
+impl Drop for Subscription<'_> {
+    fn drop(&mut self) {
+        let _ = self.remove();
+    }
+}
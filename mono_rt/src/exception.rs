@@ -0,0 +1,107 @@
+use crate::{MonoClass, MonoObject, MonoString};
+use std::ptr::{null_mut, NonNull};
+
+/// A managed exception thrown while running managed code
+#[derive(Debug, Clone)]
+pub struct MonoException {
+    class_name: String,
+    message: String,
+    stack_trace: Option<String>,
+    inner: Option<Box<MonoException>>,
+    hresult: i32,
+}
+
+impl std::fmt::Display for MonoException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.class_name, self.message)
+    }
+}
+
+impl std::error::Error for MonoException {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl MonoException {
+    /// # Safety
+    /// `obj` has to be a live instance of `System.Exception` or a subclass of it
+    pub unsafe fn from_object(obj: &MonoObject<'_>) -> Self {
+        let class = obj.get_class();
+        let class_name = match class.get_namespace().as_str() {
+            "" => class.get_name(),
+            namespace => format!("{}.{}", namespace, class.get_name()),
+        };
+
+        let message = call_getter(obj, "get_Message")
+            .map(|s| MonoString::new(s.ptr as *mut _).to_string())
+            .unwrap_or_default();
+        let stack_trace = call_getter(obj, "get_StackTrace")
+            .map(|s| MonoString::new(s.ptr as *mut _).to_string());
+        let inner = call_getter(obj, "get_InnerException")
+            .map(|inner| Box::new(MonoException::from_object(&inner)));
+        let hresult = call_getter(obj, "get_HResult")
+            .map(|boxed| *(boxed.unbox() as *const i32))
+            .unwrap_or(0);
+
+        Self {
+            class_name,
+            message,
+            stack_trace,
+            inner,
+            hresult,
+        }
+    }
+
+    /// Full name of the exception class, e.g. `System.NullReferenceException`
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// `None` if the exception was never thrown
+    pub fn stack_trace(&self) -> Option<&str> {
+        self.stack_trace.as_deref()
+    }
+
+    pub fn inner(&self) -> Option<&MonoException> {
+        self.inner.as_deref()
+    }
+
+    pub fn hresult(&self) -> i32 {
+        self.hresult
+    }
+}
+
+/// Turns the `exc` out-parameter of the mono invoke functions into a result
+pub(crate) fn check<T>(value: T, exc: *mut sys::MonoObject) -> Result<T, MonoException> {
+    if exc.is_null() {
+        Ok(value)
+    } else {
+        Err(unsafe { MonoException::from_object(&MonoObject::new(exc)) })
+    }
+}
+
+/// Calls a parameterless getter of `System.Exception` virtually on `obj`,
+/// returning `None` if it is missing, throws or returns null
+unsafe fn call_getter<'d>(obj: &MonoObject<'d>, name: &str) -> Option<MonoObject<'d>> {
+    let exception_class = MonoClass::new(NonNull::new(sys::mono_get_exception_class())?);
+    let method = exception_class.get_method_from_name(name, 0)?;
+    let method = sys::mono_object_get_virtual_method(obj.ptr, method.raw.as_ptr());
+    if method.is_null() {
+        return None;
+    }
+
+    let mut exc = null_mut();
+    let ret = sys::mono_runtime_invoke(method, obj.ptr as *mut _, null_mut(), &mut exc);
+    if exc.is_null() && !ret.is_null() {
+        Some(MonoObject::new(ret))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,171 @@
+//! Keeping managed objects alive from rust with gchandles
+//!
+//! The GC only scans the stack for references, a handle stored anywhere else
+//! has to be wrapped in a [`Gc`] or it may be collected or moved under it.
+
+use crate::{MonoArray, MonoDelegate, MonoObject, MonoString};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A handle type that can be kept alive by a gchandle
+pub trait GcTarget: sealed::Sealed {
+    fn object_ptr(&self) -> *mut sys::MonoObject;
+
+    #[doc(hidden)]
+    fn from_object_ptr(ptr: *mut sys::MonoObject) -> Self;
+}
+
+macro_rules! impl_gc_target {
+    ($($t:ident),* $(,)?) => {
+        $(
+            impl sealed::Sealed for $t<'_> {}
+
+            impl GcTarget for $t<'_> {
+                fn object_ptr(&self) -> *mut sys::MonoObject {
+                    self.ptr as *mut _
+                }
+
+                fn from_object_ptr(ptr: *mut sys::MonoObject) -> Self {
+                    $t::new(ptr as *mut _)
+                }
+            }
+        )*
+    };
+}
+
+impl_gc_target!(MonoObject, MonoString, MonoArray, MonoDelegate);
+
+/// A managed object kept alive until the handle is dropped
+///
+/// Unpinned objects may still be moved by the GC, [`get`](Self::get) always
+/// returns where the object is now. Pinned objects stay in place, so pointers
+/// into them remain valid for as long as the handle lives.
+pub struct Gc<T: GcTarget> {
+    handle: u32,
+    pinned: bool,
+    _m: PhantomData<T>,
+}
+
+impl<T: GcTarget> Gc<T> {
+    pub fn new(value: &T) -> Self {
+        Self::with_pinning(value, false)
+    }
+
+    pub fn pinned(value: &T) -> Self {
+        Self::with_pinning(value, true)
+    }
+
+    fn with_pinning(value: &T, pinned: bool) -> Self {
+        let handle = unsafe { sys::mono_gchandle_new(value.object_ptr(), pinned as _) };
+        Self {
+            handle,
+            pinned,
+            _m: PhantomData,
+        }
+    }
+
+    /// The object, valid for as long as the borrow of the handle
+    pub fn get(&self) -> GcRef<'_, T> {
+        GcRef::new(unsafe { sys::mono_gchandle_get_target(self.handle) })
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// A weak handle to the same object
+    pub fn downgrade(&self) -> WeakGc<T> {
+        WeakGc::new(&*self.get(), false)
+    }
+
+    pub fn as_raw(&self) -> u32 {
+        self.handle
+    }
+}
+
+/// Takes a new handle of the same kind to the object
+impl<T: GcTarget> Clone for Gc<T> {
+    fn clone(&self) -> Self {
+        Self::with_pinning(&*self.get(), self.pinned)
+    }
+}
+
+impl<T: GcTarget> Drop for Gc<T> {
+    fn drop(&mut self) {
+        unsafe { sys::mono_gchandle_free(self.handle) }
+    }
+}
+
+/// A reference to a managed object that does not keep it alive
+pub struct WeakGc<T: GcTarget> {
+    handle: u32,
+    _m: PhantomData<T>,
+}
+
+impl<T: GcTarget> WeakGc<T> {
+    /// With `track_resurrection` the object is reachable until its finalizer has run,
+    /// otherwise only until it becomes unreachable
+    pub fn new(value: &T, track_resurrection: bool) -> Self {
+        let handle =
+            unsafe { sys::mono_gchandle_new_weakref(value.object_ptr(), track_resurrection as _) };
+        Self {
+            handle,
+            _m: PhantomData,
+        }
+    }
+
+    /// The object, or `None` if it has been collected
+    ///
+    /// The object can still be collected while the borrow lives,
+    /// use [`upgrade`](Self::upgrade) to hold on to it
+    pub fn get(&self) -> Option<GcRef<'_, T>> {
+        let ptr = unsafe { sys::mono_gchandle_get_target(self.handle) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(GcRef::new(ptr))
+        }
+    }
+
+    /// A strong handle to the object, or `None` if it has been collected
+    pub fn upgrade(&self) -> Option<Gc<T>> {
+        self.get().map(|value| Gc::new(&*value))
+    }
+
+    pub fn as_raw(&self) -> u32 {
+        self.handle
+    }
+}
+
+impl<T: GcTarget> Drop for WeakGc<T> {
+    fn drop(&mut self) {
+        unsafe { sys::mono_gchandle_free(self.handle) }
+    }
+}
+
+/// An object borrowed from a [`Gc`] or [`WeakGc`]
+pub struct GcRef<'a, T> {
+    value: T,
+    _m: PhantomData<&'a ()>,
+}
+
+impl<T: GcTarget> GcRef<'_, T> {
+    fn new(ptr: *mut sys::MonoObject) -> Self {
+        Self {
+            value: T::from_object_ptr(ptr),
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for GcRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
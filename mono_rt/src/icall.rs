@@ -0,0 +1,317 @@
+//! Marshalling across the internal call boundary, used by `#[icall]` and
+//! [`Domain::add_internal_call`]
+
+use crate::{
+    Domain, FromMono, MonoArray, MonoClass, MonoDelegate, MonoObject, MonoString, MonoStruct,
+    ToMono,
+};
+use anyhow::{anyhow, Result};
+use std::any::Any;
+use std::ffi::CString;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+/// Class thrown for errors and panics in internal calls, null means `System.Exception`
+static EXCEPTION_CLASS: AtomicPtr<sys::MonoClass> = AtomicPtr::new(null_mut());
+
+/// An internal call collected by `#[icall]`, registered by [`Domain::register_icalls`]
+#[doc(hidden)]
+pub struct Icall {
+    pub path: &'static str,
+    pub register: fn(&Domain) -> Result<()>,
+}
+
+inventory::collect!(Icall);
+
+impl Domain {
+    /// Registers every function annotated with `#[icall]` in the program
+    pub fn register_icalls(&self) -> Result<()> {
+        for icall in inventory::iter::<Icall> {
+            (icall.register)(self)
+                .map_err(|e| anyhow!("Failed to register {}: {}", icall.path, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// A type that can be received as an argument of an internal call
+pub trait IcallArg: Sized {
+    /// What the managed side passes through the C ABI
+    type Abi;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self>;
+}
+
+/// A type that can be returned from an internal call
+///
+/// # Safety
+/// An all zero `Abi` has to be a valid value, it is returned when the call throws
+pub unsafe trait IcallReturn {
+    /// What the managed side expects through the C ABI
+    type Abi;
+
+    fn into_abi(self, domain: &Domain) -> Result<Self::Abi>;
+}
+
+macro_rules! impl_icall_primitive {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl IcallArg for $t {
+                type Abi = $t;
+
+                fn from_abi(abi: Self::Abi) -> Result<Self> {
+                    Ok(abi)
+                }
+            }
+
+            unsafe impl IcallReturn for $t {
+                type Abi = $t;
+
+                fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+                    Ok(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_icall_primitive!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize, f32, f64);
+
+/// Managed booleans cross as a single byte
+impl IcallArg for bool {
+    type Abi = u8;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self> {
+        Ok(abi != 0)
+    }
+}
+
+unsafe impl IcallReturn for bool {
+    type Abi = u8;
+
+    fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+        Ok(self as u8)
+    }
+}
+
+/// Managed chars cross as a single utf-16 code unit
+impl IcallArg for char {
+    type Abi = u16;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self> {
+        std::char::from_u32(abi as u32).ok_or_else(|| anyhow!("Unpaired surrogate {:#x}", abi))
+    }
+}
+
+unsafe impl IcallReturn for char {
+    type Abi = u16;
+
+    fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+        let mut units = [0_u16; 2];
+        match self.encode_utf16(&mut units) {
+            [unit] => Ok(*unit),
+            _ => Err(anyhow!("{:?} does not fit in a System.Char", self)),
+        }
+    }
+}
+
+unsafe impl IcallReturn for () {
+    type Abi = ();
+
+    fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+        Ok(())
+    }
+}
+
+/// Value types are passed by value
+impl<T: MonoStruct> IcallArg for T {
+    type Abi = T;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self> {
+        Ok(abi)
+    }
+}
+
+unsafe impl<T: MonoStruct> IcallReturn for T {
+    type Abi = T;
+
+    fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+        Ok(self)
+    }
+}
+
+/// Handles cross the ABI without a lifetime, on the rust side they take the one of the call
+macro_rules! impl_icall_object {
+    ($($t:ident),* $(,)?) => {
+        $(
+            impl<'d> IcallArg for $t<'d> {
+                type Abi = $t<'static>;
+
+                fn from_abi(abi: Self::Abi) -> Result<Self> {
+                    Ok($t::new(abi.ptr))
+                }
+            }
+
+            unsafe impl<'d> IcallReturn for $t<'d> {
+                type Abi = $t<'static>;
+
+                fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+                    Ok($t::new(self.ptr))
+                }
+            }
+        )*
+    };
+}
+
+impl_icall_object!(MonoObject, MonoString, MonoArray, MonoDelegate);
+
+impl<T> IcallArg for *mut T {
+    type Abi = *mut T;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self> {
+        Ok(abi)
+    }
+}
+
+unsafe impl<T> IcallReturn for *mut T {
+    type Abi = *mut T;
+
+    fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+        Ok(self)
+    }
+}
+
+impl<T> IcallArg for *const T {
+    type Abi = *const T;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self> {
+        Ok(abi)
+    }
+}
+
+unsafe impl<T> IcallReturn for *const T {
+    type Abi = *const T;
+
+    fn into_abi(self, _domain: &Domain) -> Result<Self::Abi> {
+        Ok(self)
+    }
+}
+
+impl IcallArg for String {
+    type Abi = MonoString<'static>;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self> {
+        String::from_mono(&MonoObject::new(abi.ptr as *mut _))
+    }
+}
+
+unsafe impl IcallReturn for String {
+    type Abi = MonoString<'static>;
+
+    fn into_abi(self, domain: &Domain) -> Result<Self::Abi> {
+        self.as_str().into_abi(domain)
+    }
+}
+
+unsafe impl IcallReturn for &str {
+    type Abi = MonoString<'static>;
+
+    fn into_abi(self, domain: &Domain) -> Result<Self::Abi> {
+        Ok(MonoString::new(domain.create_string(self)?.ptr))
+    }
+}
+
+/// One-dimensional arrays
+impl<T: for<'d> FromMono<'d>> IcallArg for Vec<T> {
+    type Abi = MonoArray<'static>;
+
+    fn from_abi(abi: Self::Abi) -> Result<Self> {
+        Vec::from_mono(&MonoObject::new(abi.ptr as *mut _))
+    }
+}
+
+unsafe impl<T: ToMono> IcallReturn for Vec<T> {
+    type Abi = MonoArray<'static>;
+
+    fn into_abi(self, domain: &Domain) -> Result<Self::Abi> {
+        let obj = self.to_mono(domain)?;
+        Ok(MonoArray::new(obj.ptr as *mut _))
+    }
+}
+
+/// An error is thrown as a managed exception
+unsafe impl<T: IcallReturn, E: std::fmt::Display> IcallReturn for std::result::Result<T, E> {
+    type Abi = T::Abi;
+
+    fn into_abi(self, domain: &Domain) -> Result<Self::Abi> {
+        match self {
+            Ok(value) => value.into_abi(domain),
+            Err(e) => Err(anyhow!("{}", e)),
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "rust panicked".to_string()
+    }
+}
+
+/// Sets the class of the exception thrown when an internal call returns an error or panics,
+/// `None` goes back to `System.Exception`
+///
+/// The class needs a constructor taking just the message.
+///
+/// # Safety
+/// The class is kept past the borrow, it must not come from a domain that gets unloaded
+/// or be used after the root domain is dropped while it is set
+pub unsafe fn set_exception_class(class: Option<&MonoClass<'_>>) {
+    let ptr = class.map_or(null_mut(), |class| class.raw.as_ptr());
+    EXCEPTION_CLASS.store(ptr, Ordering::SeqCst);
+}
+
+/// Makes an exception pending, it is thrown once the internal call returns
+pub(crate) fn set_pending_exception(message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    unsafe {
+        let class = EXCEPTION_CLASS.load(Ordering::SeqCst);
+        let exc = if class.is_null() {
+            sys::mono_exception_from_name_msg(
+                sys::mono_get_corlib(),
+                "System\0".as_ptr() as *const _,
+                "Exception\0".as_ptr() as *const _,
+                message.as_ptr(),
+            )
+        } else {
+            sys::mono_exception_from_name_msg(
+                sys::mono_class_get_image(class),
+                sys::mono_class_get_namespace(class),
+                sys::mono_class_get_name(class),
+                message.as_ptr(),
+            )
+        };
+        sys::mono_runtime_set_pending_exception(exc, 1);
+    }
+}
+
+/// Runs the body of an internal call, turning errors and panics into a managed exception
+#[doc(hidden)]
+pub fn run<R: IcallReturn>(f: impl FnOnce() -> Result<R>) -> R::Abi {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let domain = Domain::current().ok_or_else(|| anyhow!("No domain loaded"))?;
+        f()?.into_abi(&domain)
+    }));
+
+    let message = match result {
+        Ok(Ok(abi)) => return abi,
+        Ok(Err(e)) => e.to_string(),
+        Err(payload) => panic_message(payload.as_ref()),
+    };
+    set_pending_exception(&message);
+    unsafe { std::mem::zeroed() }
+}
@@ -0,0 +1,120 @@
+//! Lazy iterators over the members of a class and the types of an image
+//!
+//! Mono walks the members with an opaque cookie that starts out null,
+//! each iterator keeps its own cookie and stops for good once mono returns null.
+
+use crate::{Domain, Image, Method, MonoClass, MonoClassField, MonoEvent, MonoProperty};
+use std::ffi::c_void;
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ptr::{null_mut, NonNull};
+
+macro_rules! class_iter {
+    ($($(#[$attr:meta])* $name:ident => $item:ident, $raw:ty, $next:ident;)*) => {
+        $(
+            $(#[$attr])*
+            pub struct $name<'d> {
+                class: NonNull<sys::MonoClass>,
+                iter: *mut c_void,
+                done: bool,
+                _m: PhantomData<&'d Domain>,
+            }
+
+            impl<'d> $name<'d> {
+                pub(crate) fn new(class: &MonoClass<'d>) -> Self {
+                    Self {
+                        class: class.raw,
+                        iter: null_mut(),
+                        done: false,
+                        _m: PhantomData,
+                    }
+                }
+            }
+
+            impl<'d> Iterator for $name<'d> {
+                type Item = $item<'d>;
+
+                fn next(&mut self) -> Option<Self::Item> {
+                    if self.done {
+                        return None;
+                    }
+                    let ptr: *mut $raw = unsafe { sys::$next(self.class.as_ptr(), &mut self.iter) };
+                    match NonNull::new(ptr) {
+                        Some(raw) => Some($item::new(raw)),
+                        None => {
+                            self.done = true;
+                            None
+                        }
+                    }
+                }
+            }
+
+            impl FusedIterator for $name<'_> {}
+        )*
+    };
+}
+
+class_iter! {
+    /// The methods declared by a class, see [`MonoClass::get_methods`]
+    Methods => Method, sys::MonoMethod, mono_class_get_methods;
+    /// The fields declared by a class, see [`MonoClass::get_fields`]
+    Fields => MonoClassField, sys::MonoClassField, mono_class_get_fields;
+    /// The properties declared by a class, see [`MonoClass::get_properties`]
+    Properties => MonoProperty, sys::MonoProperty, mono_class_get_properties;
+    /// The events declared by a class, see [`MonoClass::get_events`]
+    Events => MonoEvent, sys::MonoEvent, mono_class_get_events;
+    /// The interfaces a class declares, see [`MonoClass::get_interfaces`]
+    Interfaces => MonoClass, sys::MonoClass, mono_class_get_interfaces;
+    /// The classes nested in a class, see [`MonoClass::get_nested_types`]
+    NestedTypes => MonoClass, sys::MonoClass, mono_class_get_nested_types;
+}
+
+/// The types defined in an image, see [`Image::types`]
+pub struct Types<'d> {
+    image: NonNull<sys::MonoImage>,
+    /// Rows of the TypeDef table start at 1
+    next: u32,
+    rows: u32,
+    _m: PhantomData<&'d Domain>,
+}
+
+impl<'d> Types<'d> {
+    pub(crate) fn new(image: &Image<'d>) -> Self {
+        let rows = unsafe {
+            sys::mono_image_get_table_rows(
+                image.raw.as_ptr(),
+                sys::MonoMetaTableEnum_MONO_TABLE_TYPEDEF as _,
+            )
+        };
+        Self {
+            image: image.raw,
+            next: 1,
+            rows: rows.max(0) as u32,
+            _m: PhantomData,
+        }
+    }
+}
+
+impl<'d> Iterator for Types<'d> {
+    type Item = MonoClass<'d>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next <= self.rows {
+            let token = sys::MonoTokenType_MONO_TOKEN_TYPE_DEF | self.next;
+            self.next += 1;
+            // Types that fail to load are skipped
+            let ptr = unsafe { sys::mono_class_get(self.image.as_ptr(), token) };
+            if let Some(raw) = NonNull::new(ptr) {
+                return Some(MonoClass::new(raw));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.rows + 1 - self.next) as usize;
+        (0, Some(left))
+    }
+}
+
+impl FusedIterator for Types<'_> {}
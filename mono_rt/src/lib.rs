@@ -1,16 +1,40 @@
 pub extern crate anyhow;
+#[doc(hidden)]
+pub extern crate inventory;
 pub extern crate mono_sys as sys;
 
 pub mod assembly;
 pub mod class;
+pub mod closure;
 pub mod config;
+pub mod convert;
+#[doc(hidden)]
+pub mod derive;
 pub mod domain;
+pub mod event;
+pub mod exception;
+pub mod gc;
+pub mod icall;
+pub mod iter;
 pub mod obj;
+pub mod reload;
+pub mod resolve;
+pub mod runtime;
 
-pub use assembly::{Assembly, Image};
-pub use class::{Method, MethodSignature, MonoClass, MonoClassField};
-pub use domain::Domain;
+pub use assembly::{Assembly, AssemblyName, Image};
+pub use class::{Method, MethodDesc, MethodSignature, MonoClass, MonoClassField, MonoProperty};
+pub use closure::ClosureFn;
+pub use convert::{FromMono, MethodArgs, MonoStruct, ToMono};
+pub use domain::{Domain, InternalCallFn};
+pub use event::{MonoEvent, Subscription};
+pub use exception::MonoException;
+pub use gc::{Gc, GcRef, GcTarget, WeakGc};
+pub use icall::{IcallArg, IcallReturn};
+pub use iter::{Events, Fields, Interfaces, Methods, NestedTypes, Properties, Types};
+pub use mono_rt_macros::{icall, MonoType};
 pub use obj::*;
+pub use reload::HotReloader;
+pub use runtime::{AotMode, LogLevel, LogMessage, RuntimeBuilder};
 
 fn osstr_to_cstring(osstr: &std::ffi::OsStr) -> anyhow::Result<std::ffi::CString> {
     use std::ffi::CString;
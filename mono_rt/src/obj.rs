@@ -1,35 +1,50 @@
+use crate::class::{corlib_method, Method};
+use crate::exception::{self, MonoException};
 use crate::MonoClass;
 use crate::MonoClassField;
+use crate::{Domain, FromMono, MethodArgs};
+use anyhow::{anyhow, Result};
 use std::ffi::c_void;
-use std::ffi::CString;
+use std::ffi::CStr;
+use std::marker::PhantomData;
 use std::ptr::null_mut;
 use std::ptr::NonNull;
 
 #[repr(transparent)]
-pub struct MonoObject {
+pub struct MonoObject<'d> {
     pub(crate) ptr: *mut sys::MonoObject,
+    _m: PhantomData<&'d Domain>,
 }
 
-impl Clone for MonoObject {
+impl<'d> Clone for MonoObject<'d> {
     fn clone(&self) -> Self {
         let ptr = unsafe { sys::mono_object_clone(self.ptr) };
         Self::new(ptr)
     }
 }
 
-impl std::fmt::Display for MonoObject {
+impl std::fmt::Display for MonoObject<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", unsafe { self.to_mono_string(None) })
+        match self.to_mono_string() {
+            Ok(str) => write!(f, "{}", str),
+            Err(_) => Err(std::fmt::Error),
+        }
     }
 }
 
-impl MonoObject {
+impl<'d> MonoObject<'d> {
     pub(crate) fn new(ptr: *mut sys::MonoObject) -> Self {
-        Self { ptr }
+        Self {
+            ptr,
+            _m: PhantomData,
+        }
     }
 
     pub unsafe fn from_ptr(ptr: *mut sys::MonoObject) -> Self {
-        Self { ptr }
+        Self {
+            ptr,
+            _m: PhantomData,
+        }
     }
 
     pub fn is_null(&self) -> bool {
@@ -55,7 +70,7 @@ impl MonoObject {
         sys::mono_object_unbox(self.ptr)
     }
 
-    pub fn get_class(&self) -> MonoClass {
+    pub fn get_class(&self) -> MonoClass<'d> {
         let ptr = unsafe { sys::mono_object_get_class(self.ptr) };
         let raw = NonNull::new(ptr).unwrap();
         MonoClass::new(raw)
@@ -65,29 +80,34 @@ impl MonoObject {
         unsafe { sys::mono_object_get_size(self.ptr) }
     }
 
-    // pub unsafe fn to_mono_string(&self, exc: *mut *mut sys::MonoObject) -> MonoString {
-    //     let ptr = sys::mono_object_to_string(self.ptr, exc);
-    //     MonoString::new(ptr)
-    // }
-
-    pub unsafe fn to_mono_string(&self, exc: Option<*mut *mut sys::MonoObject>) -> MonoString {
-        let ptr = sys::mono_object_to_string(self.ptr, exc.unwrap_or(null_mut()));
-        MonoString::new(ptr)
+    /// Calls `ToString` on the object
+    pub fn to_mono_string(&self) -> Result<MonoString<'d>, MonoException> {
+        let mut exc = null_mut();
+        let ptr = unsafe { sys::mono_object_to_string(self.ptr, &mut exc) };
+        exception::check(MonoString::new(ptr), exc)
     }
 
-    pub unsafe fn get_field_value(&self, field: &MonoClassField, value: *mut std::os::raw::c_void) {
+    pub unsafe fn get_field_value(
+        &self,
+        field: &MonoClassField<'_>,
+        value: *mut std::os::raw::c_void,
+    ) {
         sys::mono_field_get_value(self.ptr, field.raw.as_ptr(), value)
     }
 }
 
 #[repr(transparent)]
-pub struct MonoArray {
+pub struct MonoArray<'d> {
     pub(crate) ptr: *mut sys::MonoArray,
+    _m: PhantomData<&'d Domain>,
 }
 
-impl MonoArray {
+impl<'d> MonoArray<'d> {
     pub(crate) fn new(ptr: *mut sys::MonoArray) -> Self {
-        Self { ptr }
+        Self {
+            ptr,
+            _m: PhantomData,
+        }
     }
 
     pub fn is_null(&self) -> bool {
@@ -105,14 +125,105 @@ impl MonoArray {
     pub unsafe fn get(&self, size: i32, index: usize) -> *mut u8 {
         sys::mono_array_addr_with_size(self.ptr, size, index) as *mut u8
     }
+
+    /// Stores `value` at `index` of an array of reference types
+    pub(crate) fn set_ref(&self, index: usize, value: &MonoObject<'_>) {
+        assert!(index < self.length(), "index out of bounds");
+        unsafe {
+            let size = std::mem::size_of::<*mut sys::MonoObject>() as i32;
+            let addr = sys::mono_array_addr_with_size(self.ptr, size, index);
+            sys::mono_gc_wbarrier_set_arrayref(self.ptr, addr as *mut c_void, value.ptr);
+        }
+    }
 }
 
+/// An instance of a delegate type, possibly multicast
 #[repr(transparent)]
-pub struct MonoString {
+pub struct MonoDelegate<'d> {
+    pub(crate) ptr: *mut sys::MonoObject,
+    _m: PhantomData<&'d Domain>,
+}
+
+impl<'d> MonoDelegate<'d> {
+    pub(crate) fn new(ptr: *mut sys::MonoObject) -> Self {
+        Self {
+            ptr,
+            _m: PhantomData,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+
+    pub fn as_ptr(&self) -> *mut sys::MonoObject {
+        self.ptr
+    }
+
+    pub fn into_raw(self) -> *mut sys::MonoObject {
+        self.ptr
+    }
+
+    pub fn as_object(&self) -> MonoObject<'d> {
+        MonoObject::new(self.ptr)
+    }
+
+    /// The `Invoke` method of the delegate type, its signature is the one of the delegate
+    pub fn invoke_method(&self) -> Result<Method<'d>> {
+        if self.is_null() {
+            return Err(anyhow!("Delegate is null"));
+        }
+        let class = self.as_object().get_class();
+        let ptr = unsafe { sys::mono_get_delegate_invoke(class.raw.as_ptr()) };
+        NonNull::new(ptr)
+            .map(Method::new)
+            .ok_or_else(|| anyhow!("{} has no Invoke method", class.get_name()))
+    }
+
+    /// Calls the delegate with a tuple of arguments checked against its `Invoke` signature
+    ///
+    /// Every target of a multicast delegate is called in order and the result of the last one
+    /// is returned, a managed exception stops the chain and is returned as an error
+    pub fn call<A: MethodArgs>(&self, args: A) -> Result<MonoObject<'d>> {
+        self.invoke_method()?.call(Some(&self.as_object()), args)
+    }
+
+    /// Like [`call`](Self::call) but converts the return value,
+    /// use `()` for delegates returning void
+    pub fn call_as<R: FromMono<'d>, A: MethodArgs>(&self, args: A) -> Result<R> {
+        R::from_mono(&self.call(args)?)
+    }
+
+    /// The single cast delegates making up this delegate, in call order
+    pub fn invocation_list(&self) -> Result<Vec<MonoDelegate<'d>>> {
+        if self.is_null() {
+            return Err(anyhow!("Delegate is null"));
+        }
+        let list = corlib_method("System.Delegate:GetInvocationList()")?
+            .call_as::<Vec<MonoObject>, _>(Some(&self.as_object()), ())?;
+        Ok(list.into_iter().map(|d| MonoDelegate::new(d.ptr)).collect())
+    }
+
+    /// Calls every target of a multicast delegate with the same arguments,
+    /// collecting each result
+    pub fn call_each<R: FromMono<'d>, A: MethodArgs>(&self, args: A) -> Result<Vec<R>> {
+        let domain = Domain::current().ok_or_else(|| anyhow!("No domain loaded"))?;
+        let args = args.to_mono_args(&domain)?;
+        let invoke = self.invoke_method()?;
+        self.invocation_list()?
+            .iter()
+            .map(|d| R::from_mono(&invoke.call_objects(Some(&d.as_object()), &args)?))
+            .collect()
+    }
+}
+
+#[repr(transparent)]
+pub struct MonoString<'d> {
     pub(crate) ptr: *mut sys::MonoString,
+    _m: PhantomData<&'d Domain>,
 }
 
-impl std::fmt::Debug for MonoString {
+impl std::fmt::Debug for MonoString<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if f.alternate() {
             write!(f, "{:#?}", self.to_string())
@@ -122,20 +233,24 @@ impl std::fmt::Debug for MonoString {
     }
 }
 
-impl std::fmt::Display for MonoString {
+impl std::fmt::Display for MonoString<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let len = self.len() as usize;
-        let str = unsafe {
-            let x = sys::mono_string_to_utf8(self.ptr) as *mut u8;
-            String::from_raw_parts(x, len, len)
-        };
-        write!(f, "{}", str)
+        // the utf-8 copy is owned by mono and has to be freed by it
+        unsafe {
+            let ptr = sys::mono_string_to_utf8(self.ptr);
+            let res = write!(f, "{}", CStr::from_ptr(ptr).to_string_lossy());
+            sys::mono_free(ptr as *mut _);
+            res
+        }
     }
 }
 
-impl MonoString {
+impl<'d> MonoString<'d> {
     pub(crate) fn new(ptr: *mut sys::MonoString) -> Self {
-        Self { ptr }
+        Self {
+            ptr,
+            _m: PhantomData,
+        }
     }
 
     pub fn is_null(&self) -> bool {
@@ -163,24 +278,30 @@ impl MonoString {
 }
 
 #[repr(transparent)]
-pub struct MonoType {
+pub struct MonoType<'d> {
     pub(crate) raw: NonNull<sys::MonoType>,
+    _m: PhantomData<&'d Domain>,
 }
 
-impl MonoType {
-    // pub(crate) fn new(ptr: *mut sys::MonoType) -> Self {
-    //     Self { ptr }
-    // }
+impl<'d> MonoType<'d> {
+    pub(crate) fn new(raw: NonNull<sys::MonoType>) -> Self {
+        Self {
+            raw,
+            _m: PhantomData,
+        }
+    }
 
     // pub fn as_ptr(&self) -> *mut sys::MonoType {
     //     self.ptr
     // }
 
     pub fn name(&self) -> String {
+        // the name is allocated for the caller and has to be freed by mono
         unsafe {
             let ptr = sys::mono_type_get_name(self.raw.as_ptr());
-            let cstr = CString::from_raw(ptr);
-            cstr.to_str().unwrap().to_string()
+            let name = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+            sys::mono_free(ptr as *mut _);
+            name
         }
     }
 
@@ -195,9 +316,29 @@ impl MonoType {
         std::alloc::Layout::from_size_align(size as usize, alignment as usize).unwrap()
     }
 
-    pub fn get_class(&self) -> MonoClass {
+    /// The class of the type, including builtin types and generic instances,
+    /// `None` for types like pointers that have none
+    pub fn get_class(&self) -> Option<MonoClass<'d>> {
+        let ptr = unsafe { sys::mono_class_from_mono_type(self.raw.as_ptr()) };
+        NonNull::new(ptr).map(MonoClass::new)
+    }
+
+    /// Whether `value` can be stored in a location of this type,
+    /// value types are expected to be boxed
+    pub fn accepts(&self, value: &MonoObject<'_>) -> bool {
         unsafe {
-            MonoClass::new(NonNull::new(sys::mono_type_get_class(self.raw.as_ptr())).unwrap())
+            let mut class = sys::mono_class_from_mono_type(self.raw.as_ptr());
+            if class.is_null() {
+                return false;
+            }
+            let nullable = sys::mono_class_is_nullable(class) != 0;
+            if value.is_null() {
+                return nullable || sys::mono_class_is_valuetype(class) == 0;
+            }
+            if nullable {
+                class = sys::mono_class_get_nullable_param(class);
+            }
+            !sys::mono_object_isinst(value.ptr, class).is_null()
         }
     }
 }
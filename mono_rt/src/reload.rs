@@ -0,0 +1,201 @@
+//! Reloading assemblies into a fresh child domain when they change on disk
+
+use crate::{Assembly, Domain};
+use anyhow::{anyhow, Result};
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::ptr::NonNull;
+use std::time::SystemTime;
+
+type LoadHook = Box<dyn FnMut(&Domain, &[Assembly<'_>]) -> Result<()>>;
+type SaveHook = Box<dyn FnMut(&Domain, &[Assembly<'_>]) -> Result<Vec<u8>>>;
+type RestoreHook = Box<dyn FnMut(&Domain, &[Assembly<'_>], &[u8]) -> Result<()>>;
+
+struct Migration {
+    save: SaveHook,
+    restore: RestoreHook,
+}
+
+/// Keeps a set of assemblies loaded in a child domain, replacing the domain when they change
+///
+/// ```ignore
+/// let mut reloader = HotReloader::new("scripts", ["Program.exe"]);
+/// reloader.migrate_state(save_world, restore_world);
+/// reloader.load()?;
+///
+/// loop {
+///     reloader.poll()?;
+///     // ...
+/// }
+/// ```
+///
+/// A reload saves the opted-in state, unloads the old domain and only then loads the new
+/// build, mono caches images by path so the old build would be picked up otherwise.
+/// Internal calls registered with `#[icall]` are registered again for every new domain,
+/// closures and anything else are registered by an [`on_load`](Self::on_load) hook.
+pub struct HotReloader {
+    friendly_name: String,
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    domain: Option<Domain>,
+    /// Loaded into `domain`, which is only ever replaced along with them,
+    /// handed out borrowed from `self`
+    assemblies: Vec<(NonNull<sys::MonoAssembly>, CString)>,
+    on_load: Vec<LoadHook>,
+    migrations: Vec<Migration>,
+    /// State saved from a domain that was unloaded, kept until a load succeeds
+    saved: Option<Vec<Vec<u8>>>,
+    generation: u32,
+}
+
+impl HotReloader {
+    /// Watches the given assemblies, nothing is loaded until [`load`](Self::load)
+    /// or [`poll`](Self::poll) is called
+    pub fn new<P: Into<PathBuf>>(friendly_name: &str, paths: impl IntoIterator<Item = P>) -> Self {
+        let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+        Self {
+            friendly_name: friendly_name.to_string(),
+            modified: vec![None; paths.len()],
+            paths,
+            domain: None,
+            assemblies: Vec::new(),
+            on_load: Vec::new(),
+            migrations: Vec::new(),
+            saved: None,
+            generation: 0,
+        }
+    }
+
+    /// Runs `f` every time the assemblies have been loaded into a new domain,
+    /// before any state is restored
+    pub fn on_load<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnMut(&Domain, &[Assembly<'_>]) -> Result<()> + 'static,
+    {
+        self.on_load.push(Box::new(f));
+        self
+    }
+
+    /// Carries state over reloads, `save` runs in the old domain before it is unloaded
+    /// and `restore` gets its bytes in the new one
+    pub fn migrate_state<S, R>(&mut self, save: S, restore: R) -> &mut Self
+    where
+        S: FnMut(&Domain, &[Assembly<'_>]) -> Result<Vec<u8>> + 'static,
+        R: FnMut(&Domain, &[Assembly<'_>], &[u8]) -> Result<()> + 'static,
+    {
+        self.migrations.push(Migration {
+            save: Box::new(save),
+            restore: Box::new(restore),
+        });
+        self
+    }
+
+    /// The child domain the assemblies are currently loaded in
+    pub fn domain(&self) -> Option<&Domain> {
+        self.domain.as_ref()
+    }
+
+    /// The assemblies in the order of their paths
+    pub fn assemblies(&self) -> Vec<Assembly<'_>> {
+        borrow_assemblies(&self.assemblies)
+    }
+
+    /// How many times the assemblies have been loaded
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Reloads if any of the assemblies changed since the last attempt to load them,
+    /// returning whether it did
+    pub fn poll(&mut self) -> Result<bool> {
+        let modified = self.modified_times();
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.load()?;
+        Ok(true)
+    }
+
+    /// Loads the assemblies into a new child domain, replacing the current one
+    ///
+    /// The old domain is kept if saving its state or unloading it fails,
+    /// if loading fails the saved state is kept for the next attempt.
+    /// The calling thread keeps its current domain, hooks run with the new one as current.
+    pub fn load(&mut self) -> Result<()> {
+        // Changes made while loading are picked up by the next poll
+        self.modified = self.modified_times();
+
+        if let Some(old) = self.domain.take() {
+            let assemblies = borrow_assemblies(&self.assemblies);
+            let saved = self
+                .migrations
+                .iter_mut()
+                .map(|m| (m.save)(&old, &assemblies))
+                .collect::<Result<Vec<_>>>();
+            let saved = match saved {
+                Ok(saved) => saved,
+                Err(e) => {
+                    self.domain = Some(old);
+                    return Err(e);
+                }
+            };
+            if let Err((old, e)) = old.try_unload() {
+                self.domain = Some(old);
+                return Err(e);
+            }
+            self.assemblies.clear();
+            self.saved = Some(saved);
+        }
+
+        // only used to create the child domain, which owns itself
+        let root = unsafe { Domain::root() }.ok_or_else(|| anyhow!("No domain loaded"))?;
+        let name = format!("{}#{}", self.friendly_name, self.generation + 1);
+        let domain = root.create_appdomain(&name, None)?;
+        // the calling thread only enters the new domain while it is being set up
+        let assemblies = domain.with_current(|| {
+            let assemblies = self
+                .paths
+                .iter()
+                .map(|path| domain.open_assembly(path))
+                .collect::<Result<Vec<_>>>()?;
+            domain.register_icalls()?;
+            for hook in &mut self.on_load {
+                hook(&domain, &assemblies)?;
+            }
+            if let Some(saved) = &self.saved {
+                for (migration, state) in self.migrations.iter_mut().zip(saved) {
+                    (migration.restore)(&domain, &assemblies, state)?;
+                }
+            }
+            Ok::<_, anyhow::Error>(assemblies)
+        })??;
+
+        self.saved = None;
+        self.assemblies = assemblies
+            .into_iter()
+            .map(|a| (a.raw, a.path_cstr))
+            .collect();
+        self.domain = Some(domain);
+        self.generation += 1;
+        Ok(())
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        self.paths
+            .iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+/// Handles to the loaded assemblies, for as long as the reloader is borrowed
+fn borrow_assemblies(raw: &[(NonNull<sys::MonoAssembly>, CString)]) -> Vec<Assembly<'_>> {
+    raw.iter()
+        .map(|(raw, path_cstr)| Assembly {
+            raw: *raw,
+            path_cstr: path_cstr.clone(),
+            _m: PhantomData,
+        })
+        .collect()
+}
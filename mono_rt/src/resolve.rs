@@ -0,0 +1,93 @@
+//! Resolving assembly references from rust and observing assembly loads
+//!
+//! Hooks are process wide and stay installed for as long as the process lives,
+//! mono has no way to remove them. They run for every lookup, so a hook should
+//! return `None` quickly for names it does not handle.
+
+use crate::{Assembly, AssemblyName, Domain};
+use std::ffi::c_void;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::{null_mut, NonNull};
+
+type ResolveHook =
+    Box<dyn for<'d> Fn(&'d Domain, &AssemblyName<'_>) -> Option<Assembly<'d>> + Send + Sync>;
+type LoadHook = Box<dyn Fn(&Assembly<'_>) + Send + Sync>;
+
+/// Runs `f` whenever mono looks for an assembly among those already loaded
+///
+/// Returning an assembly, for example one loaded with
+/// [`Domain::load_assembly_from_bytes`], answers the lookup.
+/// Search hooks also run when an assembly is loaded by path, loading the same
+/// name from within the hook recurses.
+pub fn add_search_hook<F>(f: F)
+where
+    F: for<'d> Fn(&'d Domain, &AssemblyName<'_>) -> Option<Assembly<'d>> + Send + Sync + 'static,
+{
+    let user_data = Box::into_raw(Box::new(Box::new(f) as ResolveHook));
+    unsafe {
+        sys::mono_install_assembly_search_hook(Some(search_callback), user_data as *mut c_void)
+    };
+}
+
+/// Runs `f` before mono looks for a referenced assembly on disk
+///
+/// This is the place to resolve references from custom directories or from memory,
+/// returning `None` lets mono probe its own paths.
+pub fn add_preload_hook<F>(f: F)
+where
+    F: for<'d> Fn(&'d Domain, &AssemblyName<'_>) -> Option<Assembly<'d>> + Send + Sync + 'static,
+{
+    let user_data = Box::into_raw(Box::new(Box::new(f) as ResolveHook));
+    unsafe {
+        sys::mono_install_assembly_preload_hook(Some(preload_callback), user_data as *mut c_void)
+    };
+}
+
+/// Runs `f` after every assembly load, whichever way it was loaded
+pub fn add_load_hook<F>(f: F)
+where
+    F: Fn(&Assembly<'_>) + Send + Sync + 'static,
+{
+    let user_data = Box::into_raw(Box::new(Box::new(f) as LoadHook));
+    unsafe { sys::mono_install_assembly_load_hook(Some(load_callback), user_data as *mut c_void) };
+}
+
+unsafe fn resolve(
+    aname: *mut sys::MonoAssemblyName,
+    user_data: *mut c_void,
+) -> *mut sys::MonoAssembly {
+    let f = &*(user_data as *const ResolveHook);
+    let (domain, aname) = match (Domain::current(), NonNull::new(aname)) {
+        (Some(domain), Some(aname)) => (domain, AssemblyName::new(aname)),
+        _ => return null_mut(),
+    };
+    // unwinding into the runtime is undefined behaviour
+    catch_unwind(AssertUnwindSafe(|| f(&domain, &aname)))
+        .ok()
+        .flatten()
+        .map_or(null_mut(), |assembly| assembly.raw.as_ptr())
+}
+
+unsafe extern "C" fn search_callback(
+    aname: *mut sys::MonoAssemblyName,
+    user_data: *mut c_void,
+) -> *mut sys::MonoAssembly {
+    resolve(aname, user_data)
+}
+
+unsafe extern "C" fn preload_callback(
+    aname: *mut sys::MonoAssemblyName,
+    _assemblies_path: *mut *mut c_char,
+    user_data: *mut c_void,
+) -> *mut sys::MonoAssembly {
+    resolve(aname, user_data)
+}
+
+unsafe extern "C" fn load_callback(assembly: *mut sys::MonoAssembly, user_data: *mut c_void) {
+    let f = &*(user_data as *const LoadHook);
+    if let Some(raw) = NonNull::new(assembly) {
+        let assembly = Assembly::from_raw(raw);
+        let _ = catch_unwind(AssertUnwindSafe(|| f(&assembly)));
+    }
+}
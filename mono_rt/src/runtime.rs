@@ -0,0 +1,359 @@
+//! Initializing the runtime with everything that has to be set before the JIT starts
+
+use crate::domain::{self, Domain};
+use crate::osstr_to_cstring;
+use anyhow::{anyhow, Result};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+type PrintHandler = Box<dyn Fn(&str, bool) + Send>;
+type LogHandler = Box<dyn Fn(&LogMessage) + Send + Sync>;
+
+/// Where `Console` output of the runtime itself goes, mono has no user data for it
+static PRINT_HANDLER: Mutex<Option<PrintHandler>> = Mutex::new(None);
+
+/// How much the runtime logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Error,
+    Critical,
+    Warning,
+    Message,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Critical => "critical",
+            LogLevel::Warning => "warning",
+            LogLevel::Message => "message",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+/// Which code is compiled ahead of time, see the mono documentation on AOT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AotMode {
+    None,
+    Normal,
+    Hybrid,
+    Full,
+    LlvmOnly,
+    Interp,
+    InterpLlvmOnly,
+    LlvmOnlyInterp,
+}
+
+impl AotMode {
+    fn to_raw(self) -> sys::MonoAotMode {
+        match self {
+            AotMode::None => sys::MonoAotMode_MONO_AOT_MODE_NONE,
+            AotMode::Normal => sys::MonoAotMode_MONO_AOT_MODE_NORMAL,
+            AotMode::Hybrid => sys::MonoAotMode_MONO_AOT_MODE_HYBRID,
+            AotMode::Full => sys::MonoAotMode_MONO_AOT_MODE_FULL,
+            AotMode::LlvmOnly => sys::MonoAotMode_MONO_AOT_MODE_LLVMONLY,
+            AotMode::Interp => sys::MonoAotMode_MONO_AOT_MODE_INTERP,
+            AotMode::InterpLlvmOnly => sys::MonoAotMode_MONO_AOT_MODE_INTERP_LLVMONLY,
+            AotMode::LlvmOnlyInterp => sys::MonoAotMode_MONO_AOT_MODE_LLVMONLY_INTERP,
+        }
+    }
+}
+
+/// A message logged by the runtime
+#[derive(Debug)]
+pub struct LogMessage<'a> {
+    pub domain: &'a str,
+    pub level: &'a str,
+    pub message: &'a str,
+    /// The runtime aborts after a fatal message
+    pub fatal: bool,
+}
+
+enum Config {
+    Default,
+    File(PathBuf),
+    None,
+}
+
+/// Collects the runtime options and initializes the JIT, returning the root domain
+///
+/// ```ignore
+/// let domain = RuntimeBuilder::new("game")
+///     .dirs("/usr/lib", "/etc")
+///     .debug(true)
+///     .log_level(LogLevel::Warning)
+///     .build()?;
+/// ```
+pub struct RuntimeBuilder {
+    name: String,
+    version: Option<String>,
+    dirs: Option<(PathBuf, PathBuf)>,
+    config: Config,
+    jit_options: Vec<String>,
+    debug: bool,
+    trace: Option<String>,
+    log_level: Option<LogLevel>,
+    log_mask: Option<String>,
+    signal_chaining: bool,
+    crash_chaining: bool,
+    aot_mode: Option<AotMode>,
+    log_handler: Option<LogHandler>,
+    print_handler: Option<PrintHandler>,
+}
+
+impl RuntimeBuilder {
+    /// Starts with the default configuration of the runtime,
+    /// `name` is the friendly name of the root domain
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: None,
+            dirs: None,
+            config: Config::Default,
+            jit_options: Vec::new(),
+            debug: false,
+            trace: None,
+            log_level: None,
+            log_mask: None,
+            signal_chaining: false,
+            crash_chaining: false,
+            aot_mode: None,
+            log_handler: None,
+            print_handler: None,
+        }
+    }
+
+    /// The framework version to run, e.g. `v4.0.30319`,
+    /// by default it is taken from the first assembly opened
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// The directories containing the framework assemblies and the runtime configuration
+    pub fn dirs<P: Into<PathBuf>, Q: Into<PathBuf>>(
+        mut self,
+        assembly_dir: P,
+        config_dir: Q,
+    ) -> Self {
+        self.dirs = Some((assembly_dir.into(), config_dir.into()));
+        self
+    }
+
+    /// Reads the runtime configuration from a file instead of the default one
+    pub fn config_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config = Config::File(path.into());
+        self
+    }
+
+    /// Skips reading a runtime configuration
+    pub fn no_config(mut self) -> Self {
+        self.config = Config::None;
+        self
+    }
+
+    /// A command line option of the `mono` executable, e.g. `--optimize=all`
+    ///
+    /// Mono exits the process on options it does not understand
+    pub fn jit_option(mut self, option: &str) -> Self {
+        self.jit_options.push(option.to_string());
+        self
+    }
+
+    /// Keeps the debug information of loaded assemblies for line numbers in stack traces
+    pub fn debug(mut self, enabled: bool) -> Self {
+        self.debug = enabled;
+        self
+    }
+
+    /// Traces method calls, the same as `--trace=<options>` of the `mono` executable
+    pub fn trace(mut self, options: &str) -> Self {
+        self.trace = Some(options.to_string());
+        self
+    }
+
+    pub fn log_level(mut self, level: LogLevel) -> Self {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Comma separated components to log, e.g. `asm,type`
+    pub fn log_mask(mut self, mask: &str) -> Self {
+        self.log_mask = Some(mask.to_string());
+        self
+    }
+
+    /// Lets signal handlers installed before the runtime run for signals it does not handle
+    pub fn signal_chaining(mut self, enabled: bool) -> Self {
+        self.signal_chaining = enabled;
+        self
+    }
+
+    /// Lets crash handlers installed before the runtime run after it reports a crash
+    pub fn crash_chaining(mut self, enabled: bool) -> Self {
+        self.crash_chaining = enabled;
+        self
+    }
+
+    pub fn aot_mode(mut self, mode: AotMode) -> Self {
+        self.aot_mode = Some(mode);
+        self
+    }
+
+    /// Receives the messages logged by the runtime instead of stderr
+    pub fn log_handler<F: Fn(&LogMessage) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.log_handler = Some(Box::new(f));
+        self
+    }
+
+    /// Receives what the runtime prints itself, along with whether it was meant for stdout
+    pub fn print_handler<F: Fn(&str, bool) + Send + 'static>(mut self, f: F) -> Self {
+        self.print_handler = Some(Box::new(f));
+        self
+    }
+
+    /// Applies the options and initializes the JIT, which can happen once per process
+    pub fn build(self) -> Result<Domain> {
+        if domain::jit_initialized() {
+            return Err(anyhow!("The JIT can only be initialized once per process"));
+        }
+
+        // checked before anything is applied
+        CString::new(self.name.as_str())?;
+        let dirs = match &self.dirs {
+            Some((assembly_dir, config_dir)) => {
+                for dir in [assembly_dir, config_dir] {
+                    if !dir.is_dir() {
+                        return Err(anyhow!("{} is not a directory", dir.display()));
+                    }
+                }
+                Some((
+                    osstr_to_cstring(assembly_dir.as_os_str())?,
+                    osstr_to_cstring(config_dir.as_os_str())?,
+                ))
+            }
+            None => None,
+        };
+        let config = match &self.config {
+            Config::File(path) if !path.is_file() => {
+                return Err(anyhow!("{} does not exist", path.display()))
+            }
+            Config::File(path) => Some(osstr_to_cstring(path.as_os_str())?),
+            _ => None,
+        };
+        let mut jit_options = self
+            .jit_options
+            .iter()
+            .map(|option| {
+                if !option.starts_with('-') {
+                    return Err(anyhow!("{} is not a JIT option", option));
+                }
+                Ok(CString::new(option.as_str())?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let trace = self.trace.as_deref().map(CString::new).transpose()?;
+        let log_mask = self.log_mask.as_deref().map(CString::new).transpose()?;
+
+        unsafe {
+            // mono only validates trace options by applying them, so they go first
+            // and nothing else has been applied yet if they are invalid
+            if let Some(trace) = &trace {
+                if sys::mono_jit_set_trace_options(trace.as_ptr()) == 0 {
+                    return Err(anyhow!("Invalid trace options {}", self.trace.unwrap()));
+                }
+            }
+            if let Some((assembly_dir, config_dir)) = &dirs {
+                sys::mono_set_dirs(assembly_dir.as_ptr(), config_dir.as_ptr());
+            }
+            match (&self.config, &config) {
+                (Config::Default, _) => sys::mono_config_parse(std::ptr::null()),
+                (_, Some(config)) => sys::mono_config_parse(config.as_ptr()),
+                _ => {}
+            }
+
+            sys::mono_set_signal_chaining(self.signal_chaining as _);
+            sys::mono_set_crash_chaining(self.crash_chaining as _);
+
+            if let Some(f) = self.log_handler {
+                let user_data = Box::into_raw(Box::new(f));
+                sys::mono_trace_set_log_handler(Some(log_callback), user_data as *mut c_void);
+            }
+            if let Some(f) = self.print_handler {
+                *PRINT_HANDLER.lock().unwrap() = Some(f);
+                sys::mono_trace_set_print_handler(Some(print_callback));
+                sys::mono_trace_set_printerr_handler(Some(print_callback));
+            }
+            if let Some(level) = self.log_level {
+                let level = CString::new(level.as_str()).unwrap();
+                sys::mono_trace_set_level_string(level.as_ptr());
+            }
+            if let Some(mask) = &log_mask {
+                sys::mono_trace_set_mask_string(mask.as_ptr());
+            }
+
+            if !jit_options.is_empty() {
+                let mut argv: Vec<*mut c_char> = jit_options
+                    .iter_mut()
+                    .map(|o| o.as_ptr() as *mut _)
+                    .collect();
+                sys::mono_jit_parse_options(argv.len() as i32, argv.as_mut_ptr());
+            }
+            if let Some(mode) = self.aot_mode {
+                sys::mono_jit_set_aot_mode(mode.to_raw());
+            }
+            if self.debug {
+                sys::mono_debug_init(sys::MonoDebugFormat_MONO_DEBUG_FORMAT_MONO);
+            }
+        }
+
+        Domain::init(&self.name, self.version.as_deref())
+    }
+}
+
+unsafe extern "C" fn log_callback(
+    log_domain: *const c_char,
+    log_level: *const c_char,
+    message: *const c_char,
+    fatal: sys::mono_bool,
+    user_data: *mut c_void,
+) {
+    unsafe fn str_from<'a>(ptr: *const c_char) -> std::borrow::Cow<'a, str> {
+        if ptr.is_null() {
+            "".into()
+        } else {
+            CStr::from_ptr(ptr).to_string_lossy()
+        }
+    }
+
+    let f = &*(user_data as *const LogHandler);
+    let (domain, level, message) = (str_from(log_domain), str_from(log_level), str_from(message));
+    // unwinding into the runtime is undefined behaviour
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        f(&LogMessage {
+            domain: &domain,
+            level: &level,
+            message: &message,
+            fatal: fatal != 0,
+        })
+    }));
+}
+
+unsafe extern "C" fn print_callback(string: *const c_char, is_stdout: sys::mono_bool) {
+    if string.is_null() {
+        return;
+    }
+    let string = CStr::from_ptr(string).to_string_lossy();
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        if let Some(f) = &*PRINT_HANDLER.lock().unwrap() {
+            f(&string, is_stdout != 0);
+        }
+    }));
+}
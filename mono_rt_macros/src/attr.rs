@@ -0,0 +1,47 @@
+use syn::{Attribute, Error, Lit, Meta, NestedMeta, Result};
+
+/// The `key = "value"` pairs of every `#[mono(...)]` attribute
+pub fn mono_args(attrs: &[Attribute]) -> Result<Vec<(String, String)>> {
+    let mut args = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("mono")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(Error::new_spanned(
+                    meta,
+                    "expected #[mono(key = \"value\")]",
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) => {
+                    let key = nv
+                        .path
+                        .get_ident()
+                        .ok_or_else(|| Error::new_spanned(&nv.path, "expected an identifier"))?
+                        .to_string();
+                    let value = match nv.lit {
+                        Lit::Str(s) => s.value(),
+                        lit => return Err(Error::new_spanned(lit, "expected a string")),
+                    };
+                    args.push((key, value));
+                }
+                nested => return Err(Error::new_spanned(nested, "expected key = \"value\"")),
+            }
+        }
+    }
+    Ok(args)
+}
+
+pub fn is_repr_c(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("repr"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .any(|nested| matches!(nested, NestedMeta::Meta(Meta::Path(p)) if p.is_ident("C")))
+}
@@ -0,0 +1,79 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Error, FnArg, ItemFn, LitStr, Result, ReturnType};
+
+pub fn expand(path: LitStr, item: ItemFn) -> Result<TokenStream> {
+    let sig = &item.sig;
+    let ident = &sig.ident;
+
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &sig.generics,
+            "internal calls can not be generic",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new_spanned(
+            asyncness,
+            "internal calls can not be async",
+        ));
+    }
+    if let Some(variadic) = &sig.variadic {
+        return Err(Error::new_spanned(
+            variadic,
+            "internal calls can not be variadic",
+        ));
+    }
+
+    let mut arg_idents = Vec::new();
+    let mut arg_types = Vec::new();
+    for (i, arg) in sig.inputs.iter().enumerate() {
+        match arg {
+            FnArg::Typed(arg) => {
+                arg_idents.push(format_ident!("arg{}", i));
+                arg_types.push(&arg.ty);
+            }
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new_spanned(
+                    receiver,
+                    "internal calls can not take self",
+                ))
+            }
+        }
+    }
+
+    let ret = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    let shim = format_ident!("__mono_icall_{}", ident, span = Span::call_site());
+
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            extern "C" fn #shim(
+                #(#arg_idents: <#arg_types as ::mono_rt::IcallArg>::Abi),*
+            ) -> <#ret as ::mono_rt::IcallReturn>::Abi {
+                ::mono_rt::icall::run(|| {
+                    #(
+                        let #arg_idents = <#arg_types as ::mono_rt::IcallArg>::from_abi(#arg_idents)?;
+                    )*
+                    ::std::result::Result::Ok(#ident(#(#arg_idents),*))
+                })
+            }
+
+            fn register(domain: &::mono_rt::Domain) -> ::mono_rt::anyhow::Result<()> {
+                unsafe { domain.add_internal_call_raw(#path, #shim as *const _) }
+            }
+
+            ::mono_rt::inventory::submit! {
+                ::mono_rt::icall::Icall {
+                    path: #path,
+                    register,
+                }
+            }
+        };
+    })
+}
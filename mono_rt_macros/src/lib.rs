@@ -0,0 +1,55 @@
+extern crate proc_macro;
+
+mod attr;
+mod icall;
+mod mono_type;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemFn, LitStr};
+
+/// Maps a rust struct onto a managed value type or class, implementing `ToMono` and `FromMono`
+///
+/// ```ignore
+/// #[derive(MonoType)]
+/// #[repr(C)]
+/// #[mono(namespace = "App", name = "Vec3")]
+/// struct Vec3 {
+///     x: f32,
+///     y: f32,
+///     #[mono(name = "Z")]
+///     z: f32,
+/// }
+/// ```
+///
+/// The managed definition is checked the first time the class is looked up in each domain
+/// and cached after that: field names, order and types have to match, and for `#[repr(C)]`
+/// structs mapped onto value types so do the size and the field offsets
+#[proc_macro_derive(MonoType, attributes(mono))]
+pub fn derive_mono_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    mono_type::expand(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Turns a rust function into an internal call of the managed method at the given path
+///
+/// ```ignore
+/// #[mono::icall("App.Hello::PrintString")]
+/// fn print(s: String) {
+///     println!("{}", s);
+/// }
+///
+/// domain.register_icalls()?;
+/// ```
+///
+/// Arguments and the return value are converted through `IcallArg` and `IcallReturn`,
+/// returning an `Err` or panicking throws a `System.Exception` in the calling managed code
+#[proc_macro_attribute]
+pub fn icall(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(attr as LitStr);
+    let item = parse_macro_input!(item as ItemFn);
+    icall::expand(path, item)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
@@ -0,0 +1,127 @@
+use crate::attr::{is_repr_c, mono_args};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, Result};
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "MonoType can not be derived for generic structs",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &data.fields,
+                    "MonoType can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "MonoType can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut namespace = None;
+    let mut name = None;
+    for (key, value) in mono_args(&input.attrs)? {
+        match key.as_str() {
+            "namespace" => namespace = Some(value),
+            "name" => name = Some(value),
+            _ => return Err(Error::new_spanned(ident, format!("unknown key `{}`", key))),
+        }
+    }
+    let namespace = namespace.unwrap_or_default();
+    let name = name.unwrap_or_else(|| ident.to_string());
+
+    let mut field_idents = Vec::new();
+    let mut field_types = Vec::new();
+    let mut managed_names = Vec::new();
+    for field in fields {
+        let mut managed_name = None;
+        for (key, value) in mono_args(&field.attrs)? {
+            match key.as_str() {
+                "name" => managed_name = Some(value),
+                _ => return Err(Error::new_spanned(field, format!("unknown key `{}`", key))),
+            }
+        }
+        let ident = field.ident.as_ref().unwrap();
+        managed_names.push(managed_name.unwrap_or_else(|| ident.to_string()));
+        field_idents.push(ident);
+        field_types.push(&field.ty);
+    }
+
+    let size = if is_repr_c(&input.attrs) {
+        quote!(::std::option::Option::Some(::std::mem::size_of::<Self>()))
+    } else {
+        quote!(::std::option::Option::None)
+    };
+
+    Ok(quote! {
+        impl ::mono_rt::ToMono for #ident {
+            #[allow(unused_variables)]
+            fn mono_class(
+                domain: &::mono_rt::Domain,
+            ) -> ::mono_rt::anyhow::Result<::mono_rt::MonoClass<'_>> {
+                let uninit = ::std::mem::MaybeUninit::<Self>::uninit();
+                let base = uninit.as_ptr() as usize;
+                ::mono_rt::derive::find_class::<Self>(
+                    domain,
+                    #namespace,
+                    #name,
+                    &[#(
+                        ::mono_rt::derive::Field {
+                            name: #managed_names,
+                            class: <#field_types as ::mono_rt::ToMono>::mono_class,
+                            offset: unsafe {
+                                ::std::ptr::addr_of!((*uninit.as_ptr()).#field_idents) as usize
+                            } - base,
+                        },
+                    )*],
+                    #size,
+                )
+            }
+
+            fn to_mono<'d>(
+                &self,
+                domain: &'d ::mono_rt::Domain,
+            ) -> ::mono_rt::anyhow::Result<::mono_rt::MonoObject<'d>> {
+                let class = <Self as ::mono_rt::ToMono>::mono_class(domain)?;
+                let obj = domain.create_object(&class);
+                #(
+                    ::mono_rt::derive::set_field(
+                        &obj,
+                        &class,
+                        #managed_names,
+                        &self.#field_idents,
+                        domain,
+                    )?;
+                )*
+                ::std::result::Result::Ok(obj)
+            }
+        }
+
+        impl<'d> ::mono_rt::FromMono<'d> for #ident {
+            fn from_mono(obj: &::mono_rt::MonoObject<'d>) -> ::mono_rt::anyhow::Result<Self> {
+                ::mono_rt::derive::with_current_domain(|domain| {
+                    let class = <Self as ::mono_rt::ToMono>::mono_class(domain)?;
+                    ::mono_rt::derive::expect_instance(obj, &class)?;
+                    ::std::result::Result::Ok(Self {
+                        #(
+                            #field_idents: ::mono_rt::derive::get_field(obj, &class, #managed_names)?,
+                        )*
+                    })
+                })
+            }
+        }
+    })
+}
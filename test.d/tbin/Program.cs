@@ -16,6 +16,8 @@ class Program {
         Hello.PrintString("this is mono string");
         var r_str = Hello.GetString();
         Console.WriteLine($"rust string: {r_str}");
+        Hello.Count();
+        Console.WriteLine($"rust count: {Hello.Count()}");
 
         var person = new Person("c#Person");
         person.Greet();
@@ -28,8 +30,14 @@ class Program {
     static void PrintNumber(UInt32 num, UInt32 num2) {
         Console.WriteLine($"c# num: {num}, {num2}");
     }
+
+    static void Apply(Transform transform) {
+        Console.WriteLine($"c# transformed: {transform(21)}");
+    }
 }
 
+delegate int Transform(int x);
+
 class Person {
     private string _name;
 
@@ -48,6 +56,16 @@ class Person {
     public void Greet() {
         Console.WriteLine($"Hello, {_name}");
     }
+
+    public int Health { get; set; } = 100;
+
+    public string Title { get; set; }
+
+    public event Action<int> OnDamage;
+
+    public void Damage(int amount) {
+        OnDamage?.Invoke(amount);
+    }
 }
 
 static class Hello {
@@ -59,6 +77,9 @@ static class Hello {
 
     [MethodImplAttribute(MethodImplOptions.InternalCall)]
     public static extern string GetString();
+
+    [MethodImplAttribute(MethodImplOptions.InternalCall)]
+    public static extern int Count();
 }
 
 }
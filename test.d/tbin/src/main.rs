@@ -1,40 +1,83 @@
 extern crate tlib as lib;
 
 use lib::mono;
-use std::{path::Path, ptr::null_mut};
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
 
 fn main() {
-    mono::config::load_default();
-
-    let domain = mono::Domain::new("test_domain").unwrap();
-
-    domain
-        .add_internal_call0("App.Hello::DoAThing", lib::Hello_DoAThing)
+    let domain = mono::RuntimeBuilder::new("test_domain")
+        .debug(true)
+        .build()
         .unwrap();
+
     domain
-        .add_internal_call1("App.Hello::PrintString", lib::Hello_PrintMonoString)
+        .add_internal_call("App.Hello::DoAThing", lib::hello_do_a_thing)
         .unwrap();
+    domain.register_icalls().unwrap();
+
+    let mut count = 0;
     domain
-        .add_internal_call0("App.Hello::GetString", lib::Hello_GetString)
+        .add_internal_call_closure(
+            "App.Hello::Count",
+            Mutex::new(move || {
+                count += 1;
+                count
+            }),
+        )
         .unwrap();
 
+    mono::resolve::add_load_hook(|assembly| println!("Loaded {}", assembly.get_name()));
+
     let assembly = domain.open_assembly(Path::new("Program.exe")).unwrap();
+    for reference in assembly.references() {
+        println!("{} references {}", assembly.get_name(), reference);
+    }
+    assembly.load_references().unwrap();
+    for loaded in domain.assemblies().unwrap() {
+        println!("{} is loaded", loaded.get_name());
+    }
+    let mscorlib = domain.assembly_by_name("mscorlib").unwrap();
+    assert!(mono::Image::corlib(&domain)
+        .get_class("System", "String")
+        .is_some());
+    assert_eq!(
+        mscorlib.get_name(),
+        mono::Image::corlib(&domain)
+            .get_assembly()
+            .unwrap()
+            .get_name()
+    );
 
     let image = assembly.get_image();
     let class = image.get_class("App", "Program").unwrap();
+    for ty in image.types() {
+        let by_token = image.get_class_from_token(ty.get_type_token()).unwrap();
+        assert_eq!(by_token.get_name(), ty.get_name());
+    }
+    assert!(image.get_class_exact("App", "program").is_none());
+    let person_type = domain.find_type("App.Person, Program").unwrap();
+    println!("Found {}", person_type.name());
 
     let csharp_method_call_from_rust = class.get_method_from_name("CallFromRust", 0).unwrap();
-    unsafe { csharp_method_call_from_rust.invoke(None, null_mut(), null_mut()) };
-
-    let csharp_method_print_num = class.get_method_from_name("PrintNumber", 2).unwrap();
-    unsafe {
-        let args = [
-            alloc_to_ptr(36_u32) as *const _,
-            alloc_to_ptr(42_u32) as *const _,
-        ]
-        .as_ptr() as *mut *mut _;
-        csharp_method_print_num.invoke(None, args, null_mut());
-    }
+    csharp_method_call_from_rust.call(None, ()).unwrap();
+
+    let csharp_method_print_num = class
+        .get_method_from_desc("Program:PrintNumber(uint,uint)")
+        .unwrap();
+    csharp_method_print_num
+        .call(None, (36_u32, 42_u32))
+        .unwrap();
+
+    let factor = 2;
+    let transform_class = image.get_class("App", "Transform").unwrap();
+    let transform = domain
+        .create_delegate(&transform_class, move |x: i32| x * factor)
+        .unwrap();
+    let csharp_method_apply = class.get_method_from_name("Apply", 1).unwrap();
+    let transformed: i32 = transform.call_as((5,)).unwrap();
+    println!("rust transformed: {}", transformed);
+    csharp_method_apply.call(None, (transform,)).unwrap();
 
     println!();
 
@@ -45,29 +88,51 @@ fn main() {
 
     let person_obj = domain.create_object(&person_class);
 
-    unsafe {
-        // person_obj.init()
-        let args = [domain.create_string("RustPerson").unwrap().into_raw() as *const _].as_ptr()
-            as *mut *mut _;
-        person_constructor.invoke(Some(&person_obj), args, null_mut());
+    // person_obj.init()
+    person_constructor
+        .call(Some(&person_obj), ("RustPerson",))
+        .unwrap();
 
-        // greet
-        person_method_greet.invoke(Some(&person_obj), null_mut(), null_mut());
+    // greet
+    person_method_greet.call(Some(&person_obj), ()).unwrap();
 
-        let args2 = [domain.create_string("RustPerson2").unwrap().into_raw() as *const _].as_ptr()
-            as *mut *mut _;
-        person_method_set_name.invoke(Some(&person_obj), args2, null_mut());
+    person_method_set_name
+        .call(Some(&person_obj), ("RustPerson2",))
+        .unwrap();
 
-        // greet
-        person_method_greet.invoke(Some(&person_obj), null_mut(), null_mut());
-    }
+    // greet
+    person_method_greet.call(Some(&person_obj), ()).unwrap();
+
+    let health = person_class.get_property_from_name("Health").unwrap();
+    health.set(&person_obj, 5).unwrap();
+    assert_eq!(health.get::<i32>(&person_obj).unwrap(), 5);
+    let title = person_class.get_property_from_name("Title").unwrap();
+    title.set(&person_obj, "Sir".to_string()).unwrap();
+    assert_eq!(title.get::<String>(&person_obj).unwrap(), "Sir");
+
+    let on_damage = person_class.get_event_from_name("OnDamage").unwrap();
+    let person_method_damage = person_class.get_method_from_name("Damage", 1).unwrap();
+    // Action<int> is a generic delegate
+    let damage_taken = Arc::new(AtomicI32::new(0));
+    let subscription = on_damage
+        .subscribe(&domain, Some(&person_obj), {
+            let damage_taken = damage_taken.clone();
+            move |amount: i32| {
+                println!("rust took {} damage", amount);
+                damage_taken.fetch_add(amount, Ordering::SeqCst);
+            }
+        })
+        .unwrap();
+    person_method_damage.call(Some(&person_obj), (10,)).unwrap();
+    on_damage.raise(Some(&person_obj), (20,)).unwrap();
+    assert_eq!(damage_taken.load(Ordering::SeqCst), 30);
+    drop(subscription);
+    // no longer subscribed
+    person_method_damage.call(Some(&person_obj), (30,)).unwrap();
+    assert_eq!(damage_taken.load(Ordering::SeqCst), 30);
 
     println!();
 
     let ret = assembly.exec();
     println!("\nret: {}", ret);
 }
-
-fn alloc_to_ptr<T>(t: T) -> *const T {
-    Box::into_raw(Box::new(t))
-}
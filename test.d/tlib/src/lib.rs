@@ -5,20 +5,16 @@ pub extern "C" fn HelloWorldRust() {
     println!("Hello, from rust!");
 }
 
-#[no_mangle]
-pub extern "C" fn Hello_DoAThing() {
+pub fn hello_do_a_thing() {
     println!("Hello, from hello, from rust");
 }
 
-#[no_mangle]
-pub extern "C" fn Hello_PrintMonoString(s: mono::MonoString) {
+#[mono::icall("App.Hello::PrintString")]
+pub fn print_string(s: String) {
     println!("rust print: {}", s);
 }
 
-#[no_mangle]
-pub extern "C" fn Hello_GetString() -> mono::MonoString {
-    mono::Domain::get()
-        .unwrap()
-        .create_string("string from rust")
-        .unwrap()
+#[mono::icall("App.Hello::GetString")]
+pub fn get_string() -> &'static str {
+    "string from rust"
 }